@@ -0,0 +1,2 @@
+DROP INDEX pastes_expires_at_idx;
+ALTER TABLE pastes DROP COLUMN expires_at
@@ -0,0 +1,2 @@
+ALTER TABLE pastes ADD COLUMN expires_at TIMESTAMP;
+CREATE INDEX pastes_expires_at_idx ON pastes (expires_at) WHERE expires_at IS NOT NULL
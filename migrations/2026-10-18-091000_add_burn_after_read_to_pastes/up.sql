@@ -0,0 +1 @@
+ALTER TABLE pastes ADD COLUMN burn_after_read BOOLEAN NOT NULL DEFAULT FALSE
@@ -0,0 +1,2 @@
+DROP INDEX pastes_slug_idx;
+ALTER TABLE pastes DROP COLUMN slug
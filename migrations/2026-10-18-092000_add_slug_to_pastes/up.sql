@@ -0,0 +1,4 @@
+ALTER TABLE pastes ADD COLUMN slug VARCHAR(64);
+UPDATE pastes SET slug = substr(md5(random()::text || id::text), 1, 12);
+ALTER TABLE pastes ALTER COLUMN slug SET NOT NULL;
+CREATE UNIQUE INDEX pastes_slug_idx ON pastes (slug)
@@ -0,0 +1 @@
+DROP TABLE paste_revisions
@@ -0,0 +1,13 @@
+CREATE TABLE paste_revisions (
+    id BIGSERIAL PRIMARY KEY,
+    paste_id BIGINT NOT NULL REFERENCES pastes (id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,
+    title VARCHAR(200) NOT NULL,
+    body TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (paste_id, revision)
+);
+
+-- existing pastes start their history with what they hold now
+INSERT INTO paste_revisions (paste_id, revision, title, body, created_at)
+SELECT id, 1, title, body, modified_at FROM pastes
@@ -0,0 +1,2 @@
+DROP INDEX pastes_language_idx;
+ALTER TABLE pastes DROP COLUMN language
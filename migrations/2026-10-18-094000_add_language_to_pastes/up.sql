@@ -0,0 +1,2 @@
+ALTER TABLE pastes ADD COLUMN language VARCHAR(32);
+CREATE INDEX pastes_language_idx ON pastes (language)
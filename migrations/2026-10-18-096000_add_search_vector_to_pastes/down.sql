@@ -0,0 +1,2 @@
+DROP INDEX pastes_search_vector_idx;
+ALTER TABLE pastes DROP COLUMN search_vector
@@ -0,0 +1,4 @@
+ALTER TABLE pastes ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', body), 'B')
+) STORED;
+CREATE INDEX pastes_search_vector_idx ON pastes USING GIN (search_vector)
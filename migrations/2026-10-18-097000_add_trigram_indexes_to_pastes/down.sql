@@ -0,0 +1,2 @@
+DROP INDEX pastes_body_trgm_idx;
+DROP INDEX pastes_title_trgm_idx
\ No newline at end of file
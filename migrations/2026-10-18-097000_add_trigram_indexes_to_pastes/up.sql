@@ -0,0 +1,3 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX pastes_title_trgm_idx ON pastes USING GIN (title gin_trgm_ops);
+CREATE INDEX pastes_body_trgm_idx ON pastes USING GIN (body gin_trgm_ops)
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- argon2 hash in PHC string format, null for pastes anyone with the link can read
+ALTER TABLE pastes ADD COLUMN password_hash VARCHAR(256)
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE pastes DROP COLUMN cipher, DROP COLUMN nonce
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- set for pastes encrypted in the client, the body is then base64 ciphertext
+ALTER TABLE pastes
+    ADD COLUMN cipher VARCHAR(32),
+    ADD COLUMN nonce VARCHAR(64),
+    ADD CONSTRAINT pastes_cipher_nonce CHECK ((cipher IS NULL) = (nonce IS NULL))
\ No newline at end of file
@@ -0,0 +1,5 @@
+ALTER TABLE paste_revisions ALTER COLUMN title TYPE VARCHAR(200);
+ALTER TABLE pastes
+    DROP COLUMN key_id,
+    DROP COLUMN data_key,
+    ALTER COLUMN title TYPE VARCHAR(200)
\ No newline at end of file
@@ -0,0 +1,7 @@
+-- the data key sealing title and body at rest, wrapped by master key `key_id`, both
+-- null for pastes stored in plaintext. sealed titles outgrow the old limit
+ALTER TABLE pastes
+    ADD COLUMN key_id VARCHAR(64),
+    ADD COLUMN data_key VARCHAR(128),
+    ALTER COLUMN title TYPE TEXT;
+ALTER TABLE paste_revisions ALTER COLUMN title TYPE TEXT
\ No newline at end of file
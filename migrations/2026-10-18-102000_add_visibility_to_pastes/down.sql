@@ -0,0 +1 @@
+ALTER TABLE pastes DROP COLUMN visibility, DROP COLUMN access_token_hash
\ No newline at end of file
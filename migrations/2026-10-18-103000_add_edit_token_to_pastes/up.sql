@@ -0,0 +1,3 @@
+-- sha-256 of the token handed out on creation that updates and deletes take, pastes
+-- from before have none until `issue-edit-token` hands them one
+ALTER TABLE pastes ADD COLUMN edit_token_hash VARCHAR(64)
\ No newline at end of file
@@ -0,0 +1,2 @@
+DROP TABLE paste_revisions;
+DROP TABLE pastes
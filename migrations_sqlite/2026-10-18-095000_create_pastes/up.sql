@@ -0,0 +1,25 @@
+-- sqlite has no timestamp type, times are microseconds since unix epoch
+CREATE TABLE pastes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    title VARCHAR(200) NOT NULL,
+    body TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    modified_at BIGINT NOT NULL,
+    expires_at BIGINT,
+    burn_after_read BOOLEAN NOT NULL DEFAULT 0,
+    slug VARCHAR(64) NOT NULL,
+    language VARCHAR(32)
+);
+CREATE INDEX pastes_expires_at_idx ON pastes (expires_at) WHERE expires_at IS NOT NULL;
+CREATE UNIQUE INDEX pastes_slug_idx ON pastes (slug);
+CREATE INDEX pastes_language_idx ON pastes (language);
+
+CREATE TABLE paste_revisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    paste_id BIGINT NOT NULL REFERENCES pastes (id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,
+    title VARCHAR(200) NOT NULL,
+    body TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    UNIQUE (paste_id, revision)
+)
@@ -0,0 +1 @@
+ALTER TABLE pastes DROP COLUMN version
\ No newline at end of file
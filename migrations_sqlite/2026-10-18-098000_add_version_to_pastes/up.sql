@@ -0,0 +1,2 @@
+-- bumped by every update, clients send it back in If-Match to detect lost updates
+ALTER TABLE pastes ADD COLUMN version INTEGER NOT NULL DEFAULT 1
\ No newline at end of file
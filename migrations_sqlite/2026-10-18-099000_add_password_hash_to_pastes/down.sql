@@ -0,0 +1 @@
+ALTER TABLE pastes DROP COLUMN password_hash
\ No newline at end of file
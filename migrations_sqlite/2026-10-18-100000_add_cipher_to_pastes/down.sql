@@ -0,0 +1,2 @@
+ALTER TABLE pastes DROP COLUMN nonce;
+ALTER TABLE pastes DROP COLUMN cipher
\ No newline at end of file
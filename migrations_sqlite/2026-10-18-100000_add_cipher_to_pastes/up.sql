@@ -0,0 +1,3 @@
+-- set for pastes encrypted in the client, the body is then base64 ciphertext
+ALTER TABLE pastes ADD COLUMN cipher VARCHAR(32);
+ALTER TABLE pastes ADD COLUMN nonce VARCHAR(64)
\ No newline at end of file
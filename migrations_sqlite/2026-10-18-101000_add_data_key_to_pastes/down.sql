@@ -0,0 +1,2 @@
+ALTER TABLE pastes DROP COLUMN data_key;
+ALTER TABLE pastes DROP COLUMN key_id
\ No newline at end of file
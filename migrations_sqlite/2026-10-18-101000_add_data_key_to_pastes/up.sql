@@ -0,0 +1,4 @@
+-- the data key sealing title and body at rest, wrapped by master key `key_id`, both
+-- null for pastes stored in plaintext
+ALTER TABLE pastes ADD COLUMN key_id VARCHAR(64);
+ALTER TABLE pastes ADD COLUMN data_key VARCHAR(128)
\ No newline at end of file
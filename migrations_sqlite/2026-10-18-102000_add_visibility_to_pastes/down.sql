@@ -0,0 +1,2 @@
+ALTER TABLE pastes DROP COLUMN access_token_hash;
+ALTER TABLE pastes DROP COLUMN visibility
\ No newline at end of file
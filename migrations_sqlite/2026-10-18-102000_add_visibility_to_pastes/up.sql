@@ -0,0 +1,5 @@
+-- public pastes are listed, unlisted ones reachable by link only and private ones only
+-- with the token handed out on creation, of which the sha-256 is kept
+ALTER TABLE pastes ADD COLUMN visibility VARCHAR(8) NOT NULL DEFAULT 'public'
+    CHECK (visibility IN ('public', 'unlisted', 'private'));
+ALTER TABLE pastes ADD COLUMN access_token_hash VARCHAR(64)
\ No newline at end of file
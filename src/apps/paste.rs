@@ -1,15 +1,55 @@
-use actix_web::{pred, App};
+use actix_web::pred::{self, Predicate};
+use actix_web::{App, HttpMessage, Request};
 
+use crate::common::constant::CONTENT_TYPE_FORM;
 use crate::controllers::paste::*;
 use crate::server::State;
 
+// passwords of protected pastes may be posted from a form to read them,
+// matched on the mime essence so parameters such as charset don't matter
+struct FormPredicate;
+
+impl Predicate<State> for FormPredicate {
+    fn check(&self, req: &Request, _: &State) -> bool {
+        req.content_type().eq_ignore_ascii_case(CONTENT_TYPE_FORM)
+    }
+}
+
 pub fn create(state: State) -> App<State> {
     App::with_state(state)
         .prefix("/pastes")
-        .resource("/{id}", |r| {
+        // must come before "/{id}" so that it isn't taken as a paste id
+        .resource("/diff", |r| {
+            r.route().filter(pred::Get()).with(diff_pastes);
+        }).resource("/{id}", |r| {
             r.route().filter(pred::Get()).a(get_paste_by_id);
+            r.route()
+                .filter(pred::Post())
+                .filter(FormPredicate)
+                .a(get_paste_by_id);
             r.route().filter(pred::Post()).a(update_paste_by_id);
+            r.route().filter(pred::Patch()).a(patch_paste_by_id);
             r.route().filter(pred::Delete()).a(del_paste_by_id);
+        }).resource("/{id}/decrypt", |r| {
+            r.route().filter(pred::Get()).f(get_decrypt_page);
+        }).resource("/{id}/diff", |r| {
+            r.route().filter(pred::Get()).with(diff_paste_revisions);
+        }).resource("/{id}/highlighted", |r| {
+            r.route().filter(pred::Get()).a(get_highlighted_paste);
+            r.route()
+                .filter(pred::Post())
+                .filter(FormPredicate)
+                .a(get_highlighted_paste);
+        }).resource("/{id}/raw", |r| {
+            r.route().filter(pred::Get()).a(get_raw_paste);
+            r.route()
+                .filter(pred::Post())
+                .filter(FormPredicate)
+                .a(get_raw_paste);
+        }).resource("/{id}/revisions", |r| {
+            r.route().filter(pred::Get()).a(get_paste_revision_list);
+        }).resource("/{id}/revisions/{revision}", |r| {
+            r.route().filter(pred::Get()).a(get_paste_revision);
         }).resource("", |r| {
             r.route().filter(pred::Post()).a(create_paste);
             r.route().filter(pred::Get()).with(get_paste_list);
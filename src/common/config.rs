@@ -1,8 +1,26 @@
+use std::collections::HashMap;
+
+use crate::common::constant::{MAX_SLUG_LEN, MIN_GENERATED_SLUG_LEN};
+
 #[derive(Clone, Default, Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
     pub actix: ActixConfig,
+    // postgres is used if the section is omitted
+    #[serde(default)]
+    pub database: DatabaseConfig,
     pub postgres: PostgresConfig,
+    #[serde(default)]
+    pub sqlite: SqliteConfig,
+    // purges every minute if the section is omitted
+    #[serde(default)]
+    pub reaper: ReaperConfig,
+    // slugs of 8 characters if the section is omitted
+    #[serde(default)]
+    pub paste: PasteConfig,
+    pub tcp: Option<TcpConfig>,
+    // titles and bodies are stored in plaintext if the section is omitted
+    pub encryption: Option<EncryptionConfig>,
 }
 
 #[derive(Clone, Default, Deserialize)]
@@ -16,6 +34,26 @@ pub struct ActixConfig {
     pub connections: usize,
 }
 
+#[derive(Clone, Default, Deserialize)]
+pub struct DatabaseConfig {
+    pub kind: DatabaseKind,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseKind {
+    Postgres,
+    Sqlite,
+    // nothing survives a restart, meant for local development and tests
+    Memory,
+}
+
+impl Default for DatabaseKind {
+    fn default() -> Self {
+        DatabaseKind::Postgres
+    }
+}
+
 #[derive(Clone, Default, Deserialize)]
 pub struct PostgresConfig {
     pub host: String,
@@ -24,6 +62,75 @@ pub struct PostgresConfig {
     pub database: String,
 }
 
+impl PostgresConfig {
+    pub fn database_url(&self) -> String {
+        format!(
+            "postgres://{}:{}@{}/{}",
+            self.username, self.password, self.host, self.database,
+        )
+    }
+}
+
+#[derive(Clone, Default, Deserialize)]
+pub struct SqliteConfig {
+    // database file, which has to be migrated with `migrations_sqlite`
+    pub path: String,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ReaperConfig {
+    // seconds between two purges of expired pastes
+    pub interval: u64,
+    pub batch_size: i64,
+}
+
+impl Default for ReaperConfig {
+    fn default() -> Self {
+        ReaperConfig {
+            interval: 60,
+            batch_size: 500,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct PasteConfig {
+    pub slug_length: usize,
+}
+
+impl Default for PasteConfig {
+    fn default() -> Self {
+        PasteConfig { slug_length: 8 }
+    }
+}
+
+#[derive(Clone, Default, Deserialize)]
+pub struct TcpConfig {
+    pub ip: String,
+    pub port: String,
+    // bytes accepted on one connection before it is rejected
+    pub max_size: usize,
+    // seconds to wait for EOF before storing what was received
+    pub timeout: u64,
+    // connections handled at once, any more are turned away until some close
+    pub max_connections: usize,
+    // base url pastes are announced with, defaults to the http server address
+    pub url: Option<String>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+pub struct EncryptionConfig {
+    // id of the master key wrapping the data keys of new pastes
+    pub active_key: String,
+    // base64 master keys of 32 bytes by id, older ones stay until `rotate-keys` has run
+    #[serde(default)]
+    pub keys: HashMap<String, String>,
+    // toml file of more keys in the same form, to keep them out of the config
+    pub key_file: Option<String>,
+    // pastes resealed at once by `rotate-keys`
+    pub batch_size: i64,
+}
+
 impl Config {
     pub fn load(conf_fname: &str) -> Config {
         use std::fs::read_to_string;
@@ -31,6 +138,33 @@ impl Config {
 
         let config_string =
             read_to_string(conf_fname).expect(&format!("fail to read config: {}", conf_fname));
-        toml::from_str(&config_string).unwrap()
+        let config: Config = toml::from_str(&config_string).unwrap();
+        if let Err(reason) = config.check() {
+            panic!("bad config {}: {}", conf_fname, reason);
+        }
+
+        config
+    }
+
+    // settings the types alone do not rule out
+    fn check(&self) -> Result<(), &'static str> {
+        if self.reaper.interval == 0 {
+            return Err("reaper interval must be at least a second");
+        }
+        if self.reaper.batch_size <= 0 {
+            return Err("reaper batch_size must be positive");
+        }
+        let slug_length = self.paste.slug_length;
+        if slug_length < MIN_GENERATED_SLUG_LEN || slug_length > MAX_SLUG_LEN {
+            return Err("paste slug_length must be 6 to 64");
+        }
+        if self.tcp.as_ref().map_or(false, |tcp| tcp.max_connections == 0) {
+            return Err("tcp max_connections must be positive");
+        }
+        if self.encryption.as_ref().map_or(false, |enc| enc.batch_size < 1) {
+            return Err("encryption batch_size must be positive");
+        }
+
+        Ok(())
     }
 }
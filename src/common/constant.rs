@@ -1,4 +1,72 @@
 pub const CONFIG_FILENAME: &str = "config.toml";
 pub const CONTENT_TYPE_JSON: &str = "application/json";
+pub const CONTENT_TYPE_HTML: &str = "text/html; charset=utf-8";
+pub const CONTENT_TYPE_TEXT: &str = "text/plain; charset=utf-8";
+pub const CONTENT_TYPE_MERGE_PATCH: &str = "application/merge-patch+json";
+pub const CONTENT_TYPE_FORM: &str = "application/x-www-form-urlencoded";
 pub const ERR_MSG_PAYLOAD_PARSE_ORDERBY_FAIL: &str = "parse orderby list fail";
 pub const ERR_MSG_PAYLOAD_PARSE_TIME_COND_FAIL: &str = "parse time condition fail";
+pub const ERR_MSG_PAYLOAD_BAD_TTL: &str = "ttl must be between 1 and 315360000 seconds";
+pub const MAX_PASTE_TTL_SECS: u64 = 315_360_000;
+pub const MAX_SLUG_LEN: usize = 64;
+pub const MAX_SLUG_ATTEMPTS: usize = 5;
+pub const MAX_UPDATE_ATTEMPTS: usize = 5;
+pub const MIN_CUSTOM_SLUG_LEN: usize = 3;
+// generated slugs any shorter run out quickly and are easy to guess
+pub const MIN_GENERATED_SLUG_LEN: usize = 6;
+pub const ERR_MSG_PAYLOAD_BAD_SLUG: &str =
+    "slug must be 3 to 64 letters, digits, '-' or '_' and start with a letter or digit";
+pub const ERR_MSG_PAYLOAD_RESERVED_SLUG: &str = "slug is reserved";
+pub const DIFF_CONTEXT_LINES: usize = 3;
+pub const ERR_MSG_PAYLOAD_BAD_DIFF_REVISIONS: &str = "from and to must be revision numbers";
+pub const ERR_MSG_PAYLOAD_BAD_DIFF_PASTES: &str = "a and b must be paste ids";
+// the diff takes memory in proportion to the product of the line counts of both sides
+pub const MAX_DIFF_LINES: usize = 2000;
+pub const ERR_MSG_PAYLOAD_DIFF_TOO_LARGE: &str = "cannot diff texts of more than 2000 lines";
+pub const MAX_LANGUAGE_LEN: usize = 32;
+pub const ERR_MSG_PAYLOAD_BAD_LANGUAGE: &str =
+    "language must be up to 32 lowercase letters, digits, '+', '#' or '-'";
+pub const MAX_RAW_FILENAME_LEN: usize = 100;
+pub const HEADER_PASTE_TITLE: &str = "x-paste-title";
+pub const DEFAULT_PASTE_TITLE: &str = "untitled";
+pub const MAX_PASTE_SIZE: usize = 262_144;
+pub const ERR_MSG_TOO_MANY_CONNECTIONS: &str = "too many connections, try again later";
+pub const ERR_MSG_PAYLOAD_BAD_PARAMS: &str = "parse paste options in query string fail";
+pub const ERR_MSG_PAYLOAD_NOT_UTF8: &str = "paste body must be valid utf-8";
+pub const ERR_MSG_PAYLOAD_EMPTY: &str = "paste body is empty";
+pub const ERR_MSG_PAYLOAD_TOO_LARGE: &str = "paste body is too large";
+pub const ERR_MSG_PAYLOAD_UNSUPPORTED: &str = "is not supported by the storage backend";
+pub const ERR_MSG_PAYLOAD_RELEVANCE_WITHOUT_QUERY: &str = "ordering by relevance needs a search query";
+pub const ERR_MSG_PAYLOAD_BAD_MATCH_MODE: &str =
+    "match mode must be one of prefix, contains, exact, regex or fuzzy";
+pub const ERR_MSG_PAYLOAD_BAD_PATTERN: &str = "invalid pattern";
+pub const FUZZY_MATCH_THRESHOLD: f32 = 0.3;
+pub const ERR_MSG_PAYLOAD_BAD_CURSOR: &str =
+    "cursor is invalid or taken with another orderby list";
+pub const ERR_MSG_PAYLOAD_CURSOR_WITH_OFFSET: &str = "cursor and offset cannot be combined";
+pub const DEFAULT_PAGE_LIMIT: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
+pub const ERR_MSG_PAYLOAD_BAD_PAGING: &str = "limit must be 1 to 100 and offset not negative";
+pub const ERR_MSG_PAYLOAD_CURSOR_WITH_QUERY: &str = "search results cannot be paged by cursor";
+pub const ERR_MSG_PAYLOAD_BAD_FILTER: &str = "parse filter fail";
+pub const ERR_MSG_PAYLOAD_BAD_TIME: &str =
+    "expected a date, an RFC 3339 timestamp, a span before now like -7d or seconds since epoch";
+pub const MAX_FILTER_DEPTH: usize = 32;
+// chains of `and`/`or` nest as deep as they are long
+pub const MAX_FILTER_CONDS: usize = 64;
+pub const ERR_MSG_PAYLOAD_BAD_PATCH_TYPE: &str =
+    "patches must be sent as application/merge-patch+json";
+pub const ERR_MSG_PAYLOAD_PATCH_REMOVES_REQUIRED: &str = "title and body cannot be removed";
+pub const HEADER_PASTE_PASSWORD: &str = "x-paste-password";
+pub const MAX_PASSWORD_LEN: usize = 256;
+pub const PASSWORD_SALT_LEN: usize = 16;
+pub const ERR_MSG_PAYLOAD_BAD_PASSWORD: &str = "password must be 1 to 256 characters";
+// the only cipher supported for pastes encrypted in the client, as named by WebCrypto
+pub const CIPHER_AES_GCM: &str = "aes-256-gcm";
+pub const AES_GCM_NONCE_LEN: usize = 12;
+pub const AES_GCM_TAG_LEN: usize = 16;
+pub const ERR_MSG_PAYLOAD_BAD_ENCRYPTION: &str =
+    "encrypted pastes take a base64 body and a base64 12 byte nonce for aes-256-gcm";
+pub const HEADER_PASTE_TOKEN: &str = "x-paste-token";
+pub const TOKEN_LEN: usize = 32;
+pub const HEADER_PASTE_EDIT_TOKEN: &str = "x-paste-edit-token";
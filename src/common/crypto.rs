@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+
+use failure::{err_msg, Error};
+use rand::{thread_rng, Rng};
+use ring::aead::{self, OpeningKey, SealingKey, AES_256_GCM};
+
+use crate::common::{
+    config::EncryptionConfig,
+    constant::{AES_GCM_NONCE_LEN, AES_GCM_TAG_LEN},
+    error::ServerError,
+};
+
+const KEY_LEN: usize = 32;
+
+/// Master keys by id, the active one wraps the data keys of new pastes while the others
+/// are kept to open what was sealed before a rotation
+pub struct Keyring {
+    active_id: String,
+    master_keys: HashMap<String, Vec<u8>>,
+}
+
+/// Key sealing the title and body of one paste and its revisions, stored next to them
+/// wrapped by a master key
+pub struct DataKey([u8; KEY_LEN]);
+
+impl Keyring {
+    /// Collect master keys given inline in config and in its key file
+    pub fn load(config: &EncryptionConfig) -> Result<Self, Error> {
+        let mut encoded_keys = config.keys.clone();
+        if let Some(ref key_file) = config.key_file {
+            let file_keys: HashMap<String, String> = toml::from_str(&read_to_string(key_file)?)?;
+            encoded_keys.extend(file_keys);
+        }
+
+        let mut master_keys = HashMap::new();
+        for (id, encoded) in encoded_keys {
+            match base64::decode(&encoded) {
+                Ok(ref key) if key.len() == KEY_LEN => master_keys.insert(id, key.to_owned()),
+                _ => return Err(err_msg(format!("master key {} is not 32 bytes of base64", id))),
+            };
+        }
+
+        if !master_keys.contains_key(&config.active_key) {
+            return Err(err_msg(format!("no master key {}", config.active_key)));
+        }
+
+        Ok(Keyring {
+            active_id: config.active_key.to_owned(),
+            master_keys,
+        })
+    }
+
+    pub fn active_id(&self) -> &str {
+        &self.active_id
+    }
+
+    /// A fresh data key, along with it wrapped by the active master key
+    pub fn generate(&self) -> Result<(DataKey, String), ServerError> {
+        let key = thread_rng().gen::<[u8; KEY_LEN]>();
+        let wrapped = seal(
+            &self.master_keys[&self.active_id],
+            self.active_id.as_bytes(),
+            &key,
+        )?;
+
+        Ok((DataKey(key), wrapped))
+    }
+
+    /// Unwrap a data key with the master key it was wrapped by
+    pub fn unwrap(&self, key_id: &str, wrapped: &str) -> Result<DataKey, ServerError> {
+        let master_key = self
+            .master_keys
+            .get(key_id)
+            .ok_or(ServerError::Crypto("unknown master key"))?;
+        let key = open(master_key, key_id.as_bytes(), wrapped)?;
+        if key.len() != KEY_LEN {
+            return Err(ServerError::Crypto("malformed data key"));
+        }
+
+        let mut data_key = [0; KEY_LEN];
+        data_key.copy_from_slice(&key);
+        Ok(DataKey(data_key))
+    }
+}
+
+impl DataKey {
+    /// Encrypt a field of a paste, its name is authenticated along so that sealed fields
+    /// cannot be swapped
+    pub fn seal(&self, field: &str, plaintext: &str) -> Result<String, ServerError> {
+        seal(&self.0, field.as_bytes(), plaintext.as_bytes())
+    }
+
+    pub fn open(&self, field: &str, sealed: &str) -> Result<String, ServerError> {
+        String::from_utf8(open(&self.0, field.as_bytes(), sealed)?)
+            .map_err(|_| ServerError::Crypto("sealed text is not utf-8"))
+    }
+}
+
+// base64 of a random nonce followed by the ciphertext and its tag
+fn seal(key: &[u8], ad: &[u8], plaintext: &[u8]) -> Result<String, ServerError> {
+    let key = SealingKey::new(&AES_256_GCM, key).map_err(|_| ServerError::Crypto("bad key"))?;
+    let nonce = thread_rng().gen::<[u8; AES_GCM_NONCE_LEN]>();
+    let mut in_out = plaintext.to_vec();
+    in_out.resize(plaintext.len() + AES_GCM_TAG_LEN, 0);
+
+    let len = aead::seal_in_place(&key, &nonce, ad, &mut in_out, AES_GCM_TAG_LEN)
+        .map_err(|_| ServerError::Crypto("fail to seal"))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&in_out[..len]);
+
+    Ok(base64::encode(&sealed))
+}
+
+fn open(key: &[u8], ad: &[u8], sealed: &str) -> Result<Vec<u8>, ServerError> {
+    let key = OpeningKey::new(&AES_256_GCM, key).map_err(|_| ServerError::Crypto("bad key"))?;
+    let mut sealed =
+        base64::decode(sealed).map_err(|_| ServerError::Crypto("sealed text is not base64"))?;
+    if sealed.len() < AES_GCM_NONCE_LEN {
+        return Err(ServerError::Crypto("sealed text is truncated"));
+    }
+
+    let (nonce, in_out) = sealed.split_at_mut(AES_GCM_NONCE_LEN);
+    aead::open_in_place(&key, nonce, ad, 0, in_out)
+        .map(|plaintext| plaintext.to_vec())
+        .map_err(|_| ServerError::Crypto("sealed text is tampered or under another key"))
+}
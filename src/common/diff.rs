@@ -0,0 +1,141 @@
+use crate::common::{
+    constant::{DIFF_CONTEXT_LINES, MAX_DIFF_LINES},
+    error::ServerError,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineKind {
+    Context,
+    Add,
+    Del,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Diff {
+    pub from: String,
+    pub to: String,
+    pub unified: String,
+    pub hunks: Vec<Hunk>,
+}
+
+/// Compute line based diff between two texts, both as unified diff and as hunks, texts
+/// of more than `MAX_DIFF_LINES` lines are refused
+pub fn diff(from: &str, old: &str, to: &str, new: &str) -> Result<Diff, ServerError> {
+    let old_lines = old.lines().collect::<Vec<_>>();
+    let new_lines = new.lines().collect::<Vec<_>>();
+    // the lcs table takes a cell for every pair of lines
+    if old_lines.len() > MAX_DIFF_LINES || new_lines.len() > MAX_DIFF_LINES {
+        return Err(ServerError::DiffTooLarge);
+    }
+
+    let ops = diff::slice(&old_lines, &new_lines)
+        .into_iter()
+        .map(|op| match op {
+            diff::Result::Both(line, _) => (LineKind::Context, *line),
+            diff::Result::Left(line) => (LineKind::Del, *line),
+            diff::Result::Right(line) => (LineKind::Add, *line),
+        }).collect::<Vec<_>>();
+
+    let hunks = group_hunks(&ops);
+    let mut unified = String::new();
+
+    if !hunks.is_empty() {
+        unified.push_str(&format!("--- {}\n+++ {}\n", from, to));
+    }
+    for hunk in &hunks {
+        unified.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            format_range(hunk.old_start, hunk.old_lines),
+            format_range(hunk.new_start, hunk.new_lines)
+        ));
+
+        for line in &hunk.lines {
+            let prefix = match line.kind {
+                LineKind::Context => ' ',
+                LineKind::Add => '+',
+                LineKind::Del => '-',
+            };
+            unified.push(prefix);
+            unified.push_str(&line.text);
+            unified.push('\n');
+        }
+    }
+
+    Ok(Diff {
+        from: from.to_owned(),
+        to: to.to_owned(),
+        unified,
+        hunks,
+    })
+}
+
+fn group_hunks(ops: &[(LineKind, &str)]) -> Vec<Hunk> {
+    let changes = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, (kind, _))| *kind != LineKind::Context)
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+
+    // merge changes whose context would overlap into one range of ops
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for idx in changes {
+        let start = idx.saturating_sub(DIFF_CONTEXT_LINES);
+        let end = (idx + 1 + DIFF_CONTEXT_LINES).min(ops.len());
+
+        match ranges.last_mut() {
+            Some(ref mut range) if start <= range.1 => range.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let count = |ops: &[(LineKind, &str)], kind: LineKind| {
+                ops.iter().filter(|(op_kind, _)| *op_kind != kind).count()
+            };
+            // lines before the hunk, lines only in the other side don't count
+            let old_before = count(&ops[..start], LineKind::Add);
+            let new_before = count(&ops[..start], LineKind::Del);
+            let old_lines = count(&ops[start..end], LineKind::Add);
+            let new_lines = count(&ops[start..end], LineKind::Del);
+
+            Hunk {
+                old_start: if old_lines == 0 { old_before } else { old_before + 1 },
+                old_lines,
+                new_start: if new_lines == 0 { new_before } else { new_before + 1 },
+                new_lines,
+                lines: ops[start..end]
+                    .iter()
+                    .map(|(kind, text)| DiffLine {
+                        kind: *kind,
+                        text: text.to_string(),
+                    }).collect(),
+            }
+        }).collect()
+}
+
+fn format_range(start: usize, lines: usize) -> String {
+    if lines == 1 {
+        start.to_string()
+    } else {
+        format!("{},{}", start, lines)
+    }
+}
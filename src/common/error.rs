@@ -1,5 +1,3 @@
-use std::num::ParseIntError;
-
 use actix::MailboxError;
 use actix_web::{
     error::{JsonPayloadError, PayloadError, ResponseError as ActixResponseError},
@@ -19,6 +17,36 @@ pub enum ServerError {
     MailBox(#[cause] MailboxError),
     #[fail(display = "r2d2 error")]
     R2d2(#[cause] R2d2Error),
+    #[fail(display = "data not found")]
+    NotFound,
+    #[fail(display = "fail to allocate an unique slug")]
+    SlugExhausted,
+    #[fail(display = "slug already taken: {}", _0)]
+    SlugTaken(String),
+    #[fail(display = "not supported by the storage backend: {}", _0)]
+    Unsupported(&'static str),
+    #[fail(display = "invalid pattern: {}", _0)]
+    InvalidPattern(String),
+    #[fail(display = "paste is at another version")]
+    VersionMismatch,
+    #[fail(display = "password missing or wrong")]
+    WrongPassword,
+    #[fail(display = "edit token missing or wrong")]
+    WrongEditToken,
+    #[fail(display = "fail to hash password")]
+    PasswordHash(#[cause] argon2::Error),
+    #[fail(display = "body is encrypted in the client")]
+    Encrypted,
+    #[fail(display = "fail to seal or open a paste: {}", _0)]
+    Crypto(&'static str),
+    #[fail(display = "too many lines to diff")]
+    DiffTooLarge,
+}
+
+impl From<DieselError> for ServerError {
+    fn from(err: DieselError) -> Self {
+        ServerError::Database(err)
+    }
 }
 
 #[derive(Debug, Fail)]
@@ -28,9 +56,19 @@ pub enum UserError {
     #[fail(display = "bad payload: {}", _0)]
     PayloadError(String),
     #[fail(display = "bad id")]
-    BadID(#[cause] ParseIntError),
+    BadID,
     #[fail(display = "data not found")]
     NotFound,
+    #[fail(display = "slug already taken")]
+    SlugConflict,
+    #[fail(display = "paste has been changed since it was fetched")]
+    PreconditionFailed,
+    #[fail(display = "paste is protected by a password, missing or wrong")]
+    PasswordRequired,
+    #[fail(display = "paste is encrypted in the client, the server cannot read its body")]
+    Encrypted,
+    #[fail(display = "changing a paste takes the edit token given on its creation")]
+    EditTokenRequired,
     #[fail(display = "code: {}, msg: {}", code, msg)]
     Custom { code: u16, msg: String },
 }
@@ -51,17 +89,30 @@ impl From<ServerError> for UserError {
                 &DieselError::NotFound => UserError::NotFound,
                 _ => UserError::InternalError,
             },
+            ServerError::NotFound => UserError::NotFound,
+            ServerError::SlugTaken(_) => UserError::SlugConflict,
+            ServerError::VersionMismatch => UserError::PreconditionFailed,
+            ServerError::WrongPassword => UserError::PasswordRequired,
+            ServerError::Encrypted => UserError::Encrypted,
+            ServerError::WrongEditToken => UserError::EditTokenRequired,
+            ServerError::DiffTooLarge => {
+                UserError::PayloadError(constant::ERR_MSG_PAYLOAD_DIFF_TOO_LARGE.to_owned())
+            }
+            ServerError::Unsupported(feature) => UserError::PayloadError(format!(
+                "{} {}",
+                feature,
+                constant::ERR_MSG_PAYLOAD_UNSUPPORTED
+            )),
+            ServerError::InvalidPattern(reason) => UserError::PayloadError(format!(
+                "{}: {}",
+                constant::ERR_MSG_PAYLOAD_BAD_PATTERN,
+                reason
+            )),
             _ => UserError::InternalError,
         }
     }
 }
 
-impl From<ParseIntError> for UserError {
-    fn from(err: ParseIntError) -> Self {
-        UserError::BadID(err)
-    }
-}
-
 impl From<PayloadError> for UserError {
     fn from(err: PayloadError) -> Self {
         UserError::PayloadError(err.to_string())
@@ -91,7 +142,7 @@ impl ActixResponseError for UserError {
                 code: StatusCode::BAD_REQUEST.as_u16(),
                 msg: msg.to_string(),
             },
-            UserError::BadID(_) => ResponseError {
+            UserError::BadID => ResponseError {
                 code: StatusCode::BAD_REQUEST.as_u16(),
                 msg: self.to_string(),
             },
@@ -99,6 +150,26 @@ impl ActixResponseError for UserError {
                 code: StatusCode::NOT_FOUND.as_u16(),
                 msg: self.to_string(),
             },
+            UserError::SlugConflict => ResponseError {
+                code: StatusCode::CONFLICT.as_u16(),
+                msg: self.to_string(),
+            },
+            UserError::PreconditionFailed => ResponseError {
+                code: StatusCode::PRECONDITION_FAILED.as_u16(),
+                msg: self.to_string(),
+            },
+            UserError::PasswordRequired => ResponseError {
+                code: StatusCode::UNAUTHORIZED.as_u16(),
+                msg: self.to_string(),
+            },
+            UserError::Encrypted => ResponseError {
+                code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+                msg: self.to_string(),
+            },
+            UserError::EditTokenRequired => ResponseError {
+                code: StatusCode::FORBIDDEN.as_u16(),
+                msg: self.to_string(),
+            },
             UserError::Custom { ref code, ref msg } => ResponseError {
                 code: *code,
                 msg: msg.to_string(),
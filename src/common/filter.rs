@@ -0,0 +1,431 @@
+use std::fmt;
+use std::time::SystemTime;
+
+use crate::common::{
+    constant::{ERR_MSG_PAYLOAD_BAD_TIME, MAX_FILTER_CONDS, MAX_FILTER_DEPTH},
+    time,
+};
+
+/// Boolean expression of conditions on listed pastes, e.g.
+/// `title ~ "deploy" and (created_at > 2026-01-01 or language = rust)`
+#[derive(Debug, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Cond(Cond),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextOp {
+    Eq,
+    Ne,
+    // case-insensitive substring
+    Contains,
+    NotContains,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// A field compared to a value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cond {
+    Title(TextOp, String),
+    Body(TextOp, String),
+    // pastes without a language are never of the given one
+    LanguageIs(String),
+    LanguageIsNot(String),
+    CreatedAt(TimeOp, SystemTime),
+    ModifiedAt(TimeOp, SystemTime),
+}
+
+impl Filter {
+    /// Whether any of the conditions looks at paste bodies
+    pub fn mentions_body(&self) -> bool {
+        self.any_cond(|cond| match cond {
+            Cond::Body(..) => true,
+            _ => false,
+        })
+    }
+
+    /// Whether any of the conditions looks at paste titles
+    pub fn mentions_title(&self) -> bool {
+        self.any_cond(|cond| match cond {
+            Cond::Title(..) => true,
+            _ => false,
+        })
+    }
+
+    fn any_cond(&self, is_match: fn(&Cond) -> bool) -> bool {
+        match *self {
+            Filter::And(ref lhs, ref rhs) | Filter::Or(ref lhs, ref rhs) => {
+                lhs.any_cond(is_match) || rhs.any_cond(is_match)
+            }
+            Filter::Not(ref filter) => filter.any_cond(is_match),
+            Filter::Cond(ref cond) => is_match(cond),
+        }
+    }
+}
+
+impl TextOp {
+    pub fn negate(self) -> Self {
+        match self {
+            TextOp::Eq => TextOp::Ne,
+            TextOp::Ne => TextOp::Eq,
+            TextOp::Contains => TextOp::NotContains,
+            TextOp::NotContains => TextOp::Contains,
+        }
+    }
+}
+
+impl TimeOp {
+    pub fn negate(self) -> Self {
+        match self {
+            TimeOp::Eq => TimeOp::Ne,
+            TimeOp::Ne => TimeOp::Eq,
+            TimeOp::Gt => TimeOp::Le,
+            TimeOp::Ge => TimeOp::Lt,
+            TimeOp::Lt => TimeOp::Ge,
+            TimeOp::Le => TimeOp::Gt,
+        }
+    }
+}
+
+impl Cond {
+    /// Condition holding exactly where this one does not, so that sql backends can push
+    /// `not` down to the comparisons
+    pub fn negate(&self) -> Self {
+        match *self {
+            Cond::Title(op, ref value) => Cond::Title(op.negate(), value.to_owned()),
+            Cond::Body(op, ref value) => Cond::Body(op.negate(), value.to_owned()),
+            Cond::LanguageIs(ref value) => Cond::LanguageIsNot(value.to_owned()),
+            Cond::LanguageIsNot(ref value) => Cond::LanguageIs(value.to_owned()),
+            Cond::CreatedAt(op, time) => Cond::CreatedAt(op.negate(), time),
+            Cond::ModifiedAt(op, time) => Cond::ModifiedAt(op.negate(), time),
+        }
+    }
+}
+
+/// Why a filter failed to parse, columns are counted in characters from 1
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub column: usize,
+    pub reason: String,
+}
+
+impl ParseError {
+    fn new<S: Into<String>>(column: usize, reason: S) -> Self {
+        ParseError {
+            column,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at column {}", self.reason, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Op(&'static str),
+    Word(String),
+    Str(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+            Token::Op(op) => write!(f, "`{}`", op),
+            Token::Word(ref word) => write!(f, "`{}`", word),
+            Token::Str(ref string) => write!(f, "`\"{}\"`", string),
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || "_-.:+#".contains(c)
+}
+
+/// Split input into tokens along with the columns they start at
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let chars = input.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        let column = pos + 1;
+
+        let token = match c {
+            _ if c.is_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            '(' => {
+                pos += 1;
+                Token::LParen
+            }
+            ')' => {
+                pos += 1;
+                Token::RParen
+            }
+            '=' | '~' | '!' | '>' | '<' => {
+                let op = match (c, chars.get(pos + 1)) {
+                    ('=', _) => "=",
+                    ('~', _) => "~",
+                    ('!', Some('=')) => "!=",
+                    ('!', Some('~')) => "!~",
+                    ('>', Some('=')) => ">=",
+                    ('>', _) => ">",
+                    ('<', Some('=')) => "<=",
+                    ('<', _) => "<",
+                    _ => return Err(ParseError::new(column, "unexpected character `!`")),
+                };
+                pos += op.len();
+                Token::Op(op)
+            }
+            '"' => {
+                let mut string = String::new();
+                pos += 1;
+
+                loop {
+                    match chars.get(pos) {
+                        None => return Err(ParseError::new(column, "unterminated string")),
+                        Some('"') => break,
+                        // backslash takes the next character as is, quotes included
+                        Some('\\') if pos + 1 < chars.len() => {
+                            string.push(chars[pos + 1]);
+                            pos += 2;
+                        }
+                        Some(&c) => {
+                            string.push(c);
+                            pos += 1;
+                        }
+                    }
+                }
+                pos += 1;
+                Token::Str(string)
+            }
+            _ if is_word_char(c) => {
+                let start = pos;
+                while pos < chars.len() && is_word_char(chars[pos]) {
+                    pos += 1;
+                }
+                Token::Word(chars[start..pos].iter().collect())
+            }
+            _ => {
+                return Err(ParseError::new(
+                    column,
+                    format!("unexpected character `{}`", c),
+                ))
+            }
+        };
+
+        tokens.push((column, token));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    // column right after the input, where running out of tokens is reported
+    end: usize,
+    depth: usize,
+    conds: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(column, _)| *column)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) => word.eq_ignore_ascii_case(keyword),
+            _ => false,
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> ParseError {
+        let found = self
+            .peek()
+            .map_or_else(|| "the end".to_owned(), Token::to_string);
+
+        ParseError::new(
+            self.column(),
+            format!("expected {}, found {}", expected, found),
+        )
+    }
+
+    // or := and ("or" and)*
+    fn parse_or(&mut self) -> Result<Filter, ParseError> {
+        let mut filter = self.parse_and()?;
+
+        while self.is_keyword("or") {
+            self.pos += 1;
+            filter = Filter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+
+        Ok(filter)
+    }
+
+    // and := unary ("and" unary)*
+    fn parse_and(&mut self) -> Result<Filter, ParseError> {
+        let mut filter = self.parse_unary()?;
+
+        while self.is_keyword("and") {
+            self.pos += 1;
+            filter = Filter::And(Box::new(filter), Box::new(self.parse_unary()?));
+        }
+
+        Ok(filter)
+    }
+
+    // unary := "not" unary | "(" or ")" | cond
+    fn parse_unary(&mut self) -> Result<Filter, ParseError> {
+        // backends walk the tree recursively too, so it must stay shallow
+        if self.depth >= MAX_FILTER_DEPTH {
+            return Err(ParseError::new(self.column(), "filter is nested too deeply"));
+        }
+        self.depth += 1;
+
+        let filter = if self.is_keyword("not") {
+            self.pos += 1;
+            Filter::Not(Box::new(self.parse_unary()?))
+        } else if let Some(Token::LParen) = self.peek() {
+            self.pos += 1;
+            let filter = self.parse_or()?;
+
+            match self.peek() {
+                Some(Token::RParen) => self.pos += 1,
+                _ => return Err(self.unexpected("`)`")),
+            }
+            filter
+        } else {
+            self.parse_cond()?
+        };
+
+        self.depth -= 1;
+        Ok(filter)
+    }
+
+    // cond := field op value
+    fn parse_cond(&mut self) -> Result<Filter, ParseError> {
+        let field_column = self.column();
+        // a chain of `and`/`or` nests one level deeper with every condition
+        if self.conds >= MAX_FILTER_CONDS {
+            return Err(ParseError::new(field_column, "filter has too many conditions"));
+        }
+        self.conds += 1;
+
+        let field = match self.peek() {
+            Some(Token::Word(field)) => field.to_lowercase(),
+            _ => return Err(self.unexpected("a field")),
+        };
+        self.pos += 1;
+
+        let op_column = self.column();
+        let op = match self.peek() {
+            Some(Token::Op(op)) => *op,
+            _ => return Err(self.unexpected("an operator")),
+        };
+        self.pos += 1;
+
+        let value_column = self.column();
+        let value = match self.peek() {
+            Some(Token::Word(value)) | Some(Token::Str(value)) => value.to_owned(),
+            _ => return Err(self.unexpected("a value")),
+        };
+        self.pos += 1;
+
+        let bad_op = || {
+            ParseError::new(
+                op_column,
+                format!("`{}` cannot be applied to {}", op, field),
+            )
+        };
+        let text_op = || match op {
+            "=" => Ok(TextOp::Eq),
+            "!=" => Ok(TextOp::Ne),
+            "~" => Ok(TextOp::Contains),
+            "!~" => Ok(TextOp::NotContains),
+            _ => Err(bad_op()),
+        };
+        let time_op = || match op {
+            "=" => Ok(TimeOp::Eq),
+            "!=" => Ok(TimeOp::Ne),
+            ">" => Ok(TimeOp::Gt),
+            ">=" => Ok(TimeOp::Ge),
+            "<" => Ok(TimeOp::Lt),
+            "<=" => Ok(TimeOp::Le),
+            _ => Err(bad_op()),
+        };
+        let time = || {
+            time::parse(&value).ok_or_else(|| {
+                ParseError::new(
+                    value_column,
+                    format!("invalid time `{}`, {}", value, ERR_MSG_PAYLOAD_BAD_TIME),
+                )
+            })
+        };
+
+        let cond = match field.as_str() {
+            "title" => Cond::Title(text_op()?, value.to_owned()),
+            "body" => Cond::Body(text_op()?, value.to_owned()),
+            "language" => match op {
+                "=" => Cond::LanguageIs(value.to_owned()),
+                "!=" => Cond::LanguageIsNot(value.to_owned()),
+                _ => return Err(bad_op()),
+            },
+            "created_at" => Cond::CreatedAt(time_op()?, time()?),
+            "modified_at" => Cond::ModifiedAt(time_op()?, time()?),
+            _ => {
+                return Err(ParseError::new(
+                    field_column,
+                    format!("unknown field `{}`", field),
+                ))
+            }
+        };
+
+        Ok(Filter::Cond(cond))
+    }
+}
+
+/// Parse a filter expression, `and` binds tighter than `or` and `not` tighter than both
+pub fn parse(input: &str) -> Result<Filter, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+        end: input.chars().count() + 1,
+        depth: 0,
+        conds: 0,
+    };
+    let filter = parser.parse_or()?;
+
+    if parser.pos < parser.tokens.len() {
+        return Err(parser.unexpected("`and`, `or` or the end"));
+    }
+
+    Ok(filter)
+}
@@ -0,0 +1,368 @@
+use crate::common::constant::MAX_LANGUAGE_LEN;
+
+struct Language {
+    name: &'static str,
+    extensions: &'static [&'static str],
+    keywords: &'static [&'static str],
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    // snippets typical for the language, used to guess it from content
+    signatures: &'static [&'static str],
+}
+
+const C_QUOTES: &[char] = &['"', '\''];
+const SCRIPT_QUOTES: &[char] = &['"', '\'', '`'];
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        name: "rust",
+        extensions: &["rs"],
+        keywords: &[
+            "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn",
+            "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+            "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+            "unsafe", "use", "where", "while",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"'],
+        signatures: &["fn ", "let mut ", "impl ", "pub fn ", "use std::", "::new(", "-> "],
+    },
+    Language {
+        name: "python",
+        extensions: &["py"],
+        keywords: &[
+            "and", "as", "assert", "break", "class", "continue", "def", "del", "elif", "else",
+            "except", "False", "finally", "for", "from", "global", "if", "import", "in", "is",
+            "lambda", "None", "not", "or", "pass", "raise", "return", "True", "try", "while",
+            "with", "yield",
+        ],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: C_QUOTES,
+        signatures: &["def ", "import ", "self.", "elif ", "__init__", "print("],
+    },
+    Language {
+        name: "javascript",
+        extensions: &["js", "mjs", "jsx", "ts", "tsx"],
+        keywords: &[
+            "async", "await", "break", "case", "catch", "class", "const", "continue", "default",
+            "delete", "else", "export", "false", "finally", "for", "function", "if", "import",
+            "in", "instanceof", "let", "new", "null", "return", "switch", "this", "throw", "true",
+            "try", "typeof", "undefined", "var", "while",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: SCRIPT_QUOTES,
+        signatures: &["function ", "const ", "=> ", "console.log", "require(", "var "],
+    },
+    Language {
+        name: "go",
+        extensions: &["go"],
+        keywords: &[
+            "break", "case", "chan", "const", "continue", "default", "defer", "else", "false",
+            "for", "func", "go", "if", "import", "interface", "map", "nil", "package", "range",
+            "return", "select", "struct", "switch", "true", "type", "var",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: SCRIPT_QUOTES,
+        signatures: &["package ", "func ", ":= ", "fmt.", "import ("],
+    },
+    Language {
+        name: "c",
+        extensions: &["c", "h", "cc", "cpp", "hpp"],
+        keywords: &[
+            "break", "case", "char", "class", "const", "continue", "default", "do", "double",
+            "else", "enum", "extern", "float", "for", "if", "int", "long", "namespace", "return",
+            "short", "sizeof", "static", "struct", "switch", "typedef", "unsigned", "void",
+            "while",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: C_QUOTES,
+        signatures: &["#include", "int main(", "printf(", "malloc(", "std::"],
+    },
+    Language {
+        name: "java",
+        extensions: &["java"],
+        keywords: &[
+            "abstract", "boolean", "break", "case", "catch", "class", "else", "extends", "false",
+            "final", "for", "if", "implements", "import", "int", "interface", "new", "null",
+            "package", "private", "protected", "public", "return", "static", "this", "throw",
+            "throws", "true", "try", "void", "while",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: C_QUOTES,
+        signatures: &["public class ", "public static void", "System.out", "private "],
+    },
+    Language {
+        name: "bash",
+        extensions: &["sh", "bash", "zsh"],
+        keywords: &[
+            "case", "do", "done", "echo", "elif", "else", "esac", "exit", "export", "fi", "for",
+            "function", "if", "in", "local", "return", "then", "while",
+        ],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: C_QUOTES,
+        signatures: &["echo ", "fi\n", "then\n", "export ", "$(", "done\n"],
+    },
+    Language {
+        name: "sql",
+        extensions: &["sql"],
+        keywords: &[
+            "ALTER", "AND", "AS", "BY", "CREATE", "DELETE", "DROP", "FROM", "GROUP", "INDEX",
+            "INSERT", "INTO", "JOIN", "LIMIT", "NOT", "NULL", "ON", "OR", "ORDER", "PRIMARY",
+            "KEY", "SELECT", "SET", "TABLE", "UPDATE", "VALUES", "WHERE",
+        ],
+        line_comment: Some("--"),
+        block_comment: Some(("/*", "*/")),
+        quotes: C_QUOTES,
+        signatures: &["SELECT ", "FROM ", "WHERE ", "CREATE TABLE", "INSERT INTO"],
+    },
+    Language {
+        name: "yaml",
+        extensions: &["yaml", "yml"],
+        keywords: &["false", "no", "null", "true", "yes"],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: C_QUOTES,
+        signatures: &["---\n", ":\n  - ", ":\n  ", "\n- "],
+    },
+    Language {
+        name: "toml",
+        extensions: &["toml"],
+        keywords: &["false", "true"],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: C_QUOTES,
+        signatures: &["[package]", "[dependencies]", "]\n", " = \""],
+    },
+    Language {
+        name: "json",
+        extensions: &["json"],
+        keywords: &["false", "null", "true"],
+        line_comment: None,
+        block_comment: None,
+        quotes: &['"'],
+        signatures: &[],
+    },
+    Language {
+        name: "html",
+        extensions: &["html", "htm", "xml"],
+        keywords: &[],
+        line_comment: None,
+        block_comment: Some(("<!--", "-->")),
+        quotes: C_QUOTES,
+        signatures: &["<html", "<div", "</", "<!DOCTYPE"],
+    },
+    Language {
+        name: "text",
+        extensions: &["txt", "log"],
+        keywords: &[],
+        line_comment: None,
+        block_comment: None,
+        quotes: &[],
+        signatures: &[],
+    },
+];
+
+// a guess from content needs at least this many different signatures
+const MIN_SIGNATURE_HITS: usize = 2;
+
+/// Check whether given string looks like a language name
+pub fn is_valid_language(language: &str) -> bool {
+    !language.is_empty()
+        && language.len() <= MAX_LANGUAGE_LEN
+        && language
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+#-".contains(c))
+}
+
+/// Guess language of a paste from extension in its title, or else from its body
+pub fn detect_language(title: &str, body: &str) -> Option<String> {
+    detect_by_extension(title)
+        .or_else(|| detect_by_content(body))
+        .map(str::to_owned)
+}
+
+fn detect_by_extension(title: &str) -> Option<&'static str> {
+    let ext = title.trim().rsplit('.').next()?.to_lowercase();
+    if ext.len() == title.trim().len() {
+        return None;
+    }
+
+    LANGUAGES
+        .iter()
+        .find(|lang| lang.extensions.contains(&ext.as_str()))
+        .map(|lang| lang.name)
+}
+
+fn detect_by_content(body: &str) -> Option<&'static str> {
+    let trimmed = body.trim_start();
+    let first_line = trimmed.lines().next().unwrap_or("");
+
+    if first_line.starts_with("#!") {
+        let interpreter = first_line.rsplit(|c| c == '/' || c == ' ').next()?;
+        return match interpreter {
+            python if python.starts_with("python") => Some("python"),
+            "sh" | "bash" | "zsh" => Some("bash"),
+            "node" => Some("javascript"),
+            _ => None,
+        };
+    }
+
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(body).is_ok()
+    {
+        return Some("json");
+    }
+
+    LANGUAGES
+        .iter()
+        .map(|lang| {
+            let hits = lang
+                .signatures
+                .iter()
+                .filter(|signature| body.contains(*signature))
+                .count();
+            (lang.name, hits)
+        }).filter(|(_, hits)| *hits >= MIN_SIGNATURE_HITS)
+        .max_by_key(|(_, hits)| *hits)
+        .map(|(name, _)| name)
+}
+
+/// Render body as html with spans marking keywords, strings, comments and numbers,
+/// unknown languages are rendered as escaped plain text
+pub fn highlight(title: &str, language: Option<&str>, body: &str) -> String {
+    let spec = language.and_then(|name| LANGUAGES.iter().find(|lang| lang.name == name));
+    let code = match spec {
+        Some(spec) => highlight_code(spec, body),
+        None => escape(body),
+    };
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>{}</style>\n</head>\n<body>\n<pre class=\"highlight\">\
+         <code class=\"language-{}\">{}</code></pre>\n</body>\n</html>\n",
+        escape(title),
+        STYLE,
+        escape(language.unwrap_or("text")),
+        code
+    )
+}
+
+const STYLE: &str = ".hl-keyword{color:#a626a4}.hl-string{color:#50a14f}\
+                     .hl-comment{color:#a0a1a7;font-style:italic}.hl-number{color:#986801}";
+
+fn highlight_code(spec: &Language, body: &str) -> String {
+    let mut html = String::with_capacity(body.len() * 2);
+    let mut rest = body;
+
+    while let Some(c) = rest.chars().next() {
+        let token_len = if let Some(len) = comment_len(spec, rest) {
+            push_span(&mut html, "hl-comment", &rest[..len]);
+            len
+        } else if spec.quotes.contains(&c) {
+            let len = string_len(rest, c);
+            push_span(&mut html, "hl-string", &rest[..len]);
+            len
+        } else if c.is_ascii_digit() {
+            let len = number_len(rest);
+            push_span(&mut html, "hl-number", &rest[..len]);
+            len
+        } else if c.is_alphabetic() || c == '_' {
+            let len = word_len(rest);
+            let word = &rest[..len];
+            if spec.keywords.contains(&word) {
+                push_span(&mut html, "hl-keyword", word);
+            } else {
+                html.push_str(word);
+            }
+            len
+        } else {
+            html.push_str(&escape(&rest[..c.len_utf8()]));
+            c.len_utf8()
+        };
+
+        rest = &rest[token_len..];
+    }
+
+    html
+}
+
+fn comment_len(spec: &Language, text: &str) -> Option<usize> {
+    if let Some(start) = spec.line_comment {
+        if text.starts_with(start) {
+            return Some(text.find('\n').unwrap_or_else(|| text.len()));
+        }
+    }
+
+    if let Some((start, end)) = spec.block_comment {
+        if text.starts_with(start) {
+            return Some(
+                text[start.len()..]
+                    .find(end)
+                    .map_or(text.len(), |pos| start.len() + pos + end.len()),
+            );
+        }
+    }
+
+    None
+}
+
+// length of a quoted string including both quotes, unterminated strings end at line end
+fn string_len(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+
+    for (idx, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '\n' => return idx,
+            _ if c == quote => return idx + c.len_utf8(),
+            _ => (),
+        }
+    }
+
+    text.len()
+}
+
+fn word_len(text: &str) -> usize {
+    text.find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or_else(|| text.len())
+}
+
+fn number_len(text: &str) -> usize {
+    text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+        .unwrap_or_else(|| text.len())
+}
+
+fn push_span(html: &mut String, class: &str, text: &str) {
+    html.push_str("<span class=\"");
+    html.push_str(class);
+    html.push_str("\">");
+    html.push_str(&escape(text));
+    html.push_str("</span>");
+}
+
+/// Escape text for safe embedding in html
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
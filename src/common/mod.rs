@@ -1,3 +1,11 @@
 pub mod config;
 pub mod constant;
+pub mod crypto;
+pub mod diff;
 pub mod error;
+pub mod filter;
+pub mod highlight;
+pub mod password;
+pub mod slug;
+pub mod time;
+pub mod token;
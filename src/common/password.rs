@@ -0,0 +1,20 @@
+use argon2::{self, Config, Variant};
+use rand::{thread_rng, Rng};
+
+use crate::common::constant::PASSWORD_SALT_LEN;
+
+/// Hash a paste password with Argon2id and a random salt, encoded along with its parameters
+pub fn hash(password: &str) -> Result<String, argon2::Error> {
+    let salt = thread_rng().gen::<[u8; PASSWORD_SALT_LEN]>();
+    let config = Config {
+        variant: Variant::Argon2id,
+        ..Config::default()
+    };
+
+    argon2::hash_encoded(password.as_bytes(), &salt, &config)
+}
+
+/// Check a password against an encoded hash, a malformed hash opens nothing
+pub fn verify(encoded: &str, password: &str) -> bool {
+    argon2::verify_encoded(encoded, password.as_bytes()).unwrap_or(false)
+}
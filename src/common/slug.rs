@@ -0,0 +1,55 @@
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+use crate::common::constant::{
+    ERR_MSG_PAYLOAD_BAD_SLUG, ERR_MSG_PAYLOAD_RESERVED_SLUG, MAX_SLUG_LEN, MIN_CUSTOM_SLUG_LEN,
+};
+
+// path segments used by routes under /pastes that must never be shadowed by a slug
+const RESERVED_SLUGS: &[&str] = &[
+    "admin",
+    "api",
+    "diff",
+    "highlighted",
+    "new",
+    "raw",
+    "revisions",
+    "search",
+    "static",
+];
+
+/// Generate a random base62 slug of given length
+pub fn generate(len: usize) -> String {
+    let mut rng = thread_rng();
+
+    rng.sample_iter(&Alphanumeric).take(len).collect()
+}
+
+/// Check whether given string can be a paste slug, either generated or custom
+pub fn is_valid(slug: &str) -> bool {
+    !slug.is_empty() && slug.len() <= MAX_SLUG_LEN && slug.chars().all(is_slug_char)
+}
+
+/// Check a slug chosen by the creator, returns the reason on failure
+pub fn validate_custom(slug: &str) -> Result<(), &'static str> {
+    let starts_with_alphanumeric = slug
+        .chars()
+        .next()
+        .map_or(false, |c| c.is_ascii_alphanumeric());
+
+    if slug.len() < MIN_CUSTOM_SLUG_LEN || !is_valid(slug) || !starts_with_alphanumeric {
+        return Err(ERR_MSG_PAYLOAD_BAD_SLUG);
+    }
+
+    if RESERVED_SLUGS
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(slug))
+    {
+        return Err(ERR_MSG_PAYLOAD_RESERVED_SLUG);
+    }
+
+    Ok(())
+}
+
+fn is_slug_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
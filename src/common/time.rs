@@ -0,0 +1,43 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// Parse a point in time given as a date, an RFC 3339 timestamp, seconds since UNIX epoch
+/// or a span before now like `-7d`, dates stand for their midnight in UTC
+pub fn parse(time_str: &str) -> Option<SystemTime> {
+    parse_at(time_str, SystemTime::now())
+}
+
+/// Same as `parse` but relative times count back from `now`
+pub fn parse_at(time_str: &str, now: SystemTime) -> Option<SystemTime> {
+    if let Ok(secs) = time_str.parse::<u64>() {
+        return UNIX_EPOCH.checked_add(Duration::from_secs(secs));
+    }
+
+    if time_str.starts_with('-') {
+        return parse_relative(&time_str[1..], now);
+    }
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(time_str) {
+        return Some(SystemTime::from(time));
+    }
+
+    NaiveDate::parse_from_str(time_str, "%Y-%m-%d")
+        .ok()
+        .map(|date| SystemTime::from(DateTime::<Utc>::from_utc(date.and_hms(0, 0, 0), Utc)))
+}
+
+// `<n><unit>` with unit one of s, m, h, d or w
+fn parse_relative(span: &str, now: SystemTime) -> Option<SystemTime> {
+    let unit_secs = match span.chars().last()? {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86_400,
+        'w' => 604_800,
+        _ => return None,
+    };
+    let count = span[..span.len() - 1].parse::<u64>().ok()?;
+
+    now.checked_sub(Duration::from_secs(count.checked_mul(unit_secs)?))
+}
@@ -0,0 +1,25 @@
+use rand::{thread_rng, Rng};
+use ring::{constant_time::verify_slices_are_equal, digest};
+
+use crate::common::constant::TOKEN_LEN;
+
+/// Generate a random secret handed out once, only its hash is ever stored
+pub fn generate() -> String {
+    let bytes = thread_rng().gen::<[u8; TOKEN_LEN]>();
+
+    base64::encode_config(&bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// Hash a token with SHA-256 in hex, tokens are random enough to need no salt nor stretching
+pub fn hash(token: &str) -> String {
+    digest::digest(&digest::SHA256, token.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Check a token against its hash in constant time
+pub fn verify(hashed: &str, token: &str) -> bool {
+    verify_slices_are_equal(hashed.as_bytes(), hash(token).as_bytes()).is_ok()
+}
@@ -1,8 +1,11 @@
 macro_rules! call_ctrl {
     ($ctrl_fn: expr) => {
+        call_ctrl!($ctrl_fn, |result| HttpResponse::Ok().json(result))
+    };
+    ($ctrl_fn: expr, $respond: expr) => {
         $ctrl_fn()
-            .and_then(|result| match result {
-                Ok(result) => Ok(HttpResponse::Ok().json(result)),
+            .and_then(move |result| match result {
+                Ok(result) => Ok($respond(result)),
                 Err(err) => Err(UserError::from(err)),
             }).responder()
     };
@@ -1,35 +1,171 @@
-use actix_web::{AsyncResponder, HttpMessage, HttpRequest, HttpResponse, Query};
+use std::time::SystemTime;
+
+use actix_web::{
+    http::{header, Method, StatusCode},
+    AsyncResponder, FromRequest, HttpMessage, HttpRequest, HttpResponse, Query,
+};
 use futures::future::{self, Future};
+use serde::{Deserialize, Deserializer};
 
 use crate::common::{
     constant,
     error::{ServerError, UserError},
+    filter::{self, Filter},
+    highlight, slug, time,
 };
 use crate::controllers::FutureJsonResponse;
+use crate::models::{
+    list::{Cursor, MatchMode, Orderby, PasteListConds, TimeCondition},
+    paste::{CreatedPaste, Paste, PasteListEnvelope, PastePage, Visibility},
+};
 use crate::server::State;
-use crate::services::paste as paste_srv;
+use crate::services::{diff as diff_srv, paste as paste_srv, revision as revision_srv};
 
 pub fn get_paste_by_id(req: &HttpRequest<State>) -> FutureJsonResponse {
-    let db_chan = req.state().db_chan.clone();
+    let if_none_match = entity_tags(req, header::IF_NONE_MATCH);
 
-    call_ctrl!(|| future::ok(req.clone())
-        .and_then(|req| req.match_info()["id"].parse::<i64>())
-        .from_err()
-        .and_then(move |id| db_chan
-            .send(paste_srv::GetPasteByIdMsg { id })
-            .map_err(ServerError::MailBox)
-            .from_err()))
+    call_ctrl!(
+        || read_paste(req, false),
+        move |paste: Paste| {
+            let etag = etag(&paste);
+            // weak comparison as RFC 7232 asks for, so `W/"3"` matches as well, a
+            // burn-after-read paste is gone once read so its body is the only copy left
+            let not_modified = !paste.burn_after_read
+                && if_none_match
+                    .iter()
+                    .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+
+            if not_modified {
+                HttpResponse::NotModified().header(header::ETAG, etag).finish()
+            } else {
+                HttpResponse::Ok().header(header::ETAG, etag).json(paste)
+            }
+        }
+    )
+}
+
+pub fn get_highlighted_paste(req: &HttpRequest<State>) -> FutureJsonResponse {
+    call_ctrl!(
+        || read_paste(req, true),
+        |paste: Paste| HttpResponse::Ok()
+            .content_type(constant::CONTENT_TYPE_HTML)
+            .body(highlight::highlight(
+                &paste.title,
+                paste.language.as_ref().map(String::as_str),
+                &paste.body,
+            ))
+    )
+}
+
+pub fn get_raw_paste(req: &HttpRequest<State>) -> FutureJsonResponse {
+    call_ctrl!(
+        || read_paste(req, false),
+        |paste: Paste| HttpResponse::Ok()
+            .content_type(constant::CONTENT_TYPE_TEXT)
+            .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+            .header(
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"{}\"", raw_filename(&paste)),
+            ).body(paste.body)
+    )
 }
 
+// decrypts pastes encrypted in the client with the key from the url fragment, which
+// browsers never send, so `/pastes/<id>/decrypt#<key>` is a link to the plaintext
+pub fn get_decrypt_page(_req: &HttpRequest<State>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(constant::CONTENT_TYPE_HTML)
+        .body(include_str!("../../static/decrypt.html"))
+}
+
+// password of a protected paste posted from a form, so that it stays out of urls
 #[derive(Deserialize, Serialize, Debug)]
+pub struct PasswordForm {
+    pub password: String,
+}
+
+// fetch a paste to be read, which consumes it if it is burn-after-read
+fn read_paste(
+    req: &HttpRequest<State>,
+    plaintext: bool,
+) -> impl Future<Item = Result<Paste, ServerError>, Error = UserError> {
+    let db_chan = req.state().db_chan.clone();
+    let slug = parse_slug(req);
+    let credentials: Box<Future<Item = paste_srv::Credentials, Error = UserError>> =
+        if req.method() == Method::POST {
+            let token = header_value(req, constant::HEADER_PASTE_TOKEN);
+            Box::new(
+                req.urlencoded::<PasswordForm>()
+                    .map(|form| paste_srv::Credentials {
+                        password: Some(form.password),
+                        token,
+                        ..Default::default()
+                    }).map_err(|err| UserError::PayloadError(err.to_string())),
+            )
+        } else {
+            Box::new(future::ok(credentials(req)))
+        };
+
+    credentials
+        .and_then(move |credentials| -> Result<_, UserError> {
+            Ok(paste_srv::GetPasteByIdMsg {
+                slug: slug?,
+                credentials,
+                plaintext,
+            })
+        }).and_then(move |msg| db_chan.send(msg).map_err(ServerError::MailBox).from_err())
+}
+
+fn header_value(req: &HttpRequest<State>, name: &str) -> Option<String> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+}
+
+// password and tokens of a paste, sent in headers to keep them out of urls
+fn credentials(req: &HttpRequest<State>) -> paste_srv::Credentials {
+    paste_srv::Credentials {
+        password: header_value(req, constant::HEADER_PASTE_PASSWORD),
+        token: header_value(req, constant::HEADER_PASTE_TOKEN),
+        edit_token: edit_token(req),
+    }
+}
+
+// edit token as a bearer token, or in a header of its own
+fn edit_token(req: &HttpRequest<State>) -> Option<String> {
+    header_value(req, header::AUTHORIZATION.as_str())
+        .and_then(|authorization| {
+            let (scheme, token) = authorization.split_at(authorization.find(' ')?);
+            if scheme.eq_ignore_ascii_case("bearer") {
+                Some(token.trim().to_owned())
+            } else {
+                None
+            }
+        }).or_else(|| header_value(req, constant::HEADER_PASTE_EDIT_TOKEN))
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct GetPasteListConds {
+    // full-text search query
+    q: Option<String>,
     title_pat: Option<String>,
+    // prefix, contains, exact, regex or fuzzy, defaults to prefix
+    title_mode: Option<String>,
     body_pat: Option<String>,
+    body_mode: Option<String>,
+    language: Option<String>,
     cmp_created_at: Option<String>,
     cmp_modified_at: Option<String>,
+    // boolean expression over title, body, language, created_at and modified_at
+    filter: Option<String>,
     orderby_list: Option<String>,
     limit: Option<i64>,
     offset: Option<i64>,
+    // page by cursor instead of offset, empty for the first page
+    cursor: Option<String>,
+    // wrap the page in an envelope telling where it is among all pages
+    envelope: Option<bool>,
 }
 
 pub fn get_paste_list(
@@ -40,140 +176,816 @@ pub fn get_paste_list(
         .cmp_created_at
         .to_owned()
         .map_or(Ok(None), |cmp_created_at| {
-            parse_time_cond(&cmp_created_at).map(Option::from)
+            parse_time_cond("cmp_created_at", &cmp_created_at).map(Option::from)
         });
     let modified_at = conds
         .cmp_modified_at
         .to_owned()
         .map_or(Ok(None), |cmp_modified_at| {
-            parse_time_cond(&cmp_modified_at).map(Option::from)
+            parse_time_cond("cmp_modified_at", &cmp_modified_at).map(Option::from)
         });
+    let filter = conds
+        .filter
+        .as_ref()
+        .map_or(Ok(None), |filter| parse_filter(filter).map(Option::from));
     let orderby_list = conds
         .orderby_list
         .to_owned()
         .map_or(Ok(None), |orderby_list| {
             parse_orderby(&orderby_list).map(Option::from)
         });
-    let msg = paste_srv::GetPasteListMsg {
+    let language = parse_language(conds.language.to_owned());
+    let match_modes = parse_match_mode(conds.title_mode.as_ref())
+        .and_then(|title_mode| Ok((title_mode, parse_match_mode(conds.body_mode.as_ref())?)));
+    let cursor = conds
+        .cursor
+        .as_ref()
+        .map_or(Ok(None), |cursor| parse_cursor(cursor).map(Option::from));
+    // listings are paged even if the client asks for no limit
+    let limit = conds.limit.or(Some(constant::DEFAULT_PAGE_LIMIT));
+    let paging = check_paging(limit, conds.offset);
+    let by_cursor = conds.cursor.is_some();
+    let with_envelope = conds.envelope.unwrap_or(false);
+    let link_base = {
+        let conn_info = req.connection_info();
+        format!("{}://{}{}", conn_info.scheme(), conn_info.host(), req.path())
+    };
+    let link_conds = GetPasteListConds {
+        limit,
+        ..GetPasteListConds::clone(&conds)
+    };
+    let q = conds
+        .q
+        .as_ref()
+        .map(|q| q.trim().to_owned())
+        .filter(|q| !q.is_empty());
+    let list_conds = PasteListConds {
+        q,
         title_pat: conds.title_pat.to_owned(),
         body_pat: conds.body_pat.to_owned(),
-        limit: conds.limit,
+        limit,
         offset: conds.offset,
+        with_total: with_envelope,
         ..Default::default()
     };
 
-    call_ctrl!(|| future::ok(msg)
-        .and_then(move |mut msg| created_at.map(|created_at| {
-            msg.created_at = created_at;
-            msg
-        })).from_err()
-        .and_then(move |mut msg| modified_at.map(|modified_at| {
-            msg.modified_at = modified_at;
-            msg
-        })).from_err()
-        .and_then(move |mut msg| orderby_list.map(|orderby_list| {
-            msg.orderby_list = orderby_list;
-            msg
-        })).and_then(move |msg| db_chan.send(msg).map_err(ServerError::MailBox).from_err()))
+    call_ctrl!(
+        || future::result(paging.map(|_| list_conds))
+            .and_then(move |mut list_conds| created_at.map(|created_at| {
+                list_conds.created_at = created_at;
+                list_conds
+            })).from_err()
+            .and_then(move |mut list_conds| modified_at.map(|modified_at| {
+                list_conds.modified_at = modified_at;
+                list_conds
+            })).from_err()
+            .and_then(move |mut list_conds| filter.map(|filter| {
+                list_conds.filter = filter;
+                list_conds
+            })).and_then(move |mut list_conds| orderby_list.map(|orderby_list| {
+                list_conds.orderby_list = orderby_list;
+                list_conds
+            })).and_then(check_relevance_orderby)
+            .and_then(move |mut list_conds| language.map(|language| {
+                list_conds.language = language;
+                list_conds
+            })).and_then(move |mut list_conds| match_modes.map(|(title_mode, body_mode)| {
+                list_conds.title_mode = title_mode;
+                list_conds.body_mode = body_mode;
+                list_conds
+            })).and_then(move |mut list_conds| cursor.map(|cursor| {
+                list_conds.cursor = cursor;
+                list_conds
+            })).and_then(check_cursor)
+            .and_then(move |list_conds| {
+                db_chan
+                    .send(paste_srv::GetPasteListMsg(list_conds))
+                    .map_err(ServerError::MailBox)
+                    .from_err()
+            }),
+        move |page: PastePage| {
+            let mut res = HttpResponse::Ok();
+            let links = page_links(&link_base, &link_conds, &page);
+            if !links.is_empty() {
+                res.header(header::LINK, links.join(", "));
+            }
+
+            if by_cursor {
+                res.json(page)
+            } else if with_envelope {
+                let offset = link_conds.offset.unwrap_or(0);
+                let total = page.total.unwrap_or(0);
+                res.json(PasteListEnvelope {
+                    has_more: offset + (page.items.len() as i64) < total,
+                    items: page.items,
+                    total,
+                    limit: link_conds.limit,
+                    offset,
+                })
+            } else {
+                // a bare list as before, for clients unaware of paging metadata
+                res.json(page.items)
+            }
+        }
+    )
+}
+
+// RFC 5988 links to the pages around, as far as they are known
+fn page_links(base: &str, conds: &GetPasteListConds, page: &PastePage) -> Vec<String> {
+    let link = |rel: &str, update: &Fn(&mut GetPasteListConds)| {
+        let mut conds = conds.clone();
+        update(&mut conds);
+        format!(
+            "<{}?{}>; rel=\"{}\"",
+            base,
+            serde_urlencoded::to_string(&conds).unwrap_or_default(),
+            rel
+        )
+    };
+    let mut links = vec![];
+
+    if conds.cursor.is_some() {
+        links.push(link("first", &|conds| conds.cursor = Some(String::new())));
+        if let Some(ref cursor) = page.prev_cursor {
+            links.push(link("prev", &|conds| conds.cursor = Some(cursor.to_owned())));
+        }
+        if let Some(ref cursor) = page.next_cursor {
+            links.push(link("next", &|conds| conds.cursor = Some(cursor.to_owned())));
+        }
+        return links;
+    }
+
+    // pages by offset only know their neighbours once everything is counted
+    if let (Some(total), Some(limit)) = (page.total, conds.limit) {
+        if limit <= 0 {
+            return links;
+        }
+        let offset = conds.offset.unwrap_or(0);
+        let last = (total - 1).max(0) / limit * limit;
+
+        links.push(link("first", &|conds| conds.offset = Some(0)));
+        if offset > 0 {
+            let prev = (offset - limit).max(0);
+            links.push(link("prev", &|conds| conds.offset = Some(prev)));
+        }
+        if offset + limit < total {
+            links.push(link("next", &|conds| conds.offset = Some(offset + limit)));
+        }
+        links.push(link("last", &|conds| conds.offset = Some(last)));
+    }
+
+    links
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct NewPaste {
     pub title: String,
     pub body: String,
+    // seconds until the paste expires, never expires if omitted
+    pub ttl: Option<u64>,
+    // delete the paste once it has been read
+    #[serde(default)]
+    pub burn_after_read: bool,
+    // custom slug for a stable link, a random one is generated if omitted
+    pub slug: Option<String>,
+    // detected from title extension or body if omitted
+    pub language: Option<String>,
+    // needed to read the paste then, anyone with the link can if omitted
+    pub password: Option<String>,
+    // the body is base64 ciphertext, sealed with `cipher` and `nonce` in the client
+    #[serde(default)]
+    pub encrypted: bool,
+    // aes-256-gcm if omitted, the only one supported
+    pub cipher: Option<String>,
+    pub nonce: Option<String>,
+    // public if omitted, unlisted pastes are left out of listings and private ones are
+    // only shown to whoever holds the access token handed out on creation
+    pub visibility: Option<Visibility>,
 }
 
-pub fn create_paste(req: &HttpRequest<State>) -> FutureJsonResponse {
-    use std::time::SystemTime;
+// paste options for raw uploads, which carry only the body as payload
+#[derive(Deserialize, Serialize, Debug)]
+pub struct NewPasteParams {
+    title: Option<String>,
+    ttl: Option<u64>,
+    burn_after_read: Option<bool>,
+    slug: Option<String>,
+    language: Option<String>,
+    encrypted: Option<bool>,
+    cipher: Option<String>,
+    nonce: Option<String>,
+    visibility: Option<Visibility>,
+}
 
+pub fn create_paste(req: &HttpRequest<State>) -> FutureJsonResponse {
     let db_chan = req.state().db_chan.clone();
+    let slug_length = req.state().slug_length;
+    let paste_url_base = if accepts_plain_text(req) {
+        let conn_info = req.connection_info();
+        Some(format!("{}://{}/pastes/", conn_info.scheme(), conn_info.host()))
+    } else {
+        None
+    };
+    let new_paste: Box<Future<Item = NewPaste, Error = UserError>> =
+        if req.content_type() == constant::CONTENT_TYPE_JSON {
+            Box::new(req.json().from_err())
+        } else {
+            // anything else is taken as the paste body, e.g. piped through curl
+            Box::new(read_raw_paste(req))
+        };
 
-    // this requires correct content type
-    call_ctrl!(|| req
-        .json()
-        .from_err()
-        .and_then(move |new_paste: NewPaste| db_chan
-            .send(paste_srv::CreatePasteMsg {
-                title: new_paste.title,
-                body: new_paste.body,
-                created_at: SystemTime::now(),
-            }).map_err(ServerError::MailBox)
-            .from_err()))
+    call_ctrl!(
+        || new_paste
+            .and_then(move |new_paste: NewPaste| -> Result<_, UserError> {
+                let created_at = SystemTime::now();
+                let custom_slug = parse_custom_slug(new_paste.slug)?;
+                let expires_at = parse_ttl(created_at, new_paste.ttl)?;
+                let language = parse_language(new_paste.language)?;
+                let password = parse_password(new_paste.password)?;
+                let encryption = parse_encryption(
+                    new_paste.encrypted,
+                    new_paste.cipher,
+                    new_paste.nonce,
+                    &new_paste.body,
+                )?;
+
+                Ok(paste_srv::CreatePasteMsg {
+                    title: new_paste.title,
+                    body: new_paste.body,
+                    created_at,
+                    expires_at,
+                    burn_after_read: new_paste.burn_after_read,
+                    slug: custom_slug,
+                    slug_length,
+                    language,
+                    password,
+                    encryption,
+                    visibility: new_paste.visibility.unwrap_or_default(),
+                })
+            }).and_then(move |msg| db_chan.send(msg).map_err(ServerError::MailBox).from_err()),
+        move |created: CreatedPaste| match paste_url_base {
+            // tokens follow the link on lines of their own
+            Some(ref paste_url_base) => {
+                let mut body = format!(
+                    "{}{}\nedit_token: {}\n",
+                    paste_url_base, created.paste.slug, created.edit_token
+                );
+                if let Some(ref token) = created.access_token {
+                    body.push_str(&format!("access_token: {}\n", token));
+                }
+
+                HttpResponse::Ok()
+                    .content_type(constant::CONTENT_TYPE_TEXT)
+                    .body(body)
+            }
+            None => HttpResponse::Ok().json(created),
+        }
+    )
+}
+
+// options come in the query string, json creates carry them in the payload instead
+fn read_raw_paste(req: &HttpRequest<State>) -> impl Future<Item = NewPaste, Error = UserError> {
+    let params = Query::<NewPasteParams>::extract(req)
+        .map(Query::into_inner)
+        .map_err(|_| UserError::PayloadError(constant::ERR_MSG_PAYLOAD_BAD_PARAMS.to_owned()));
+    let title = header_value(req, constant::HEADER_PASTE_TITLE);
+    let password = header_value(req, constant::HEADER_PASTE_PASSWORD);
+    let body = req.body().limit(constant::MAX_PASTE_SIZE).from_err();
+
+    future::result(params).and_then(move |params| {
+        body.and_then(move |body| -> Result<_, UserError> {
+            let body = String::from_utf8(body.to_vec()).map_err(|_| {
+                UserError::PayloadError(constant::ERR_MSG_PAYLOAD_NOT_UTF8.to_owned())
+            })?;
+
+            Ok(NewPaste {
+                title: title
+                    .or(params.title)
+                    .unwrap_or_else(|| constant::DEFAULT_PASTE_TITLE.to_owned()),
+                body,
+                ttl: params.ttl,
+                burn_after_read: params.burn_after_read.unwrap_or(false),
+                slug: params.slug,
+                language: params.language,
+                password,
+                encrypted: params.encrypted.unwrap_or(false),
+                cipher: params.cipher,
+                nonce: params.nonce,
+                visibility: params.visibility,
+            })
+        })
+    })
+}
+
+fn accepts_plain_text(req: &HttpRequest<State>) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map_or(false, |accept| accept.contains("text/plain"))
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct UpdatePaste {
-    pub id: i64,
     pub title: String,
     pub body: String,
+    // detected from title extension or body if omitted
+    pub language: Option<String>,
 }
 
 pub fn update_paste_by_id(req: &HttpRequest<State>) -> FutureJsonResponse {
-    use std::time::SystemTime;
+    let db_chan = req.state().db_chan.clone();
+    let slug = parse_slug(req);
+    let versions = parse_if_match(req);
+    let credentials = credentials(req);
+
+    call_ctrl!(
+        || req
+            .json()
+            .from_err()
+            .and_then(move |updated_paste: UpdatePaste| -> Result<_, UserError> {
+                Ok(paste_srv::UpdatePasteMsg {
+                    slug: slug?,
+                    language: Some(parse_language(updated_paste.language)?),
+                    title: Some(updated_paste.title),
+                    body: Some(updated_paste.body),
+                    modified_at: SystemTime::now(),
+                    versions,
+                    credentials,
+                })
+            }).and_then(move |msg| db_chan.send(msg).map_err(ServerError::MailBox).from_err()),
+        |paste: Paste| HttpResponse::Ok().header(header::ETAG, etag(&paste)).json(paste)
+    )
+}
+
+/// RFC 7396 merge patch of a paste, members left out are kept and `null` removes one
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PastePatch {
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub title: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub body: Option<Option<String>>,
+    // removing the language has it detected again
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub language: Option<Option<String>>,
+}
 
+// tells a member set to null apart from a missing one, which serde takes as `None` alike
+fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+pub fn patch_paste_by_id(req: &HttpRequest<State>) -> FutureJsonResponse {
     let db_chan = req.state().db_chan.clone();
+    let slug = parse_slug(req);
+    let versions = parse_if_match(req);
+    let credentials = credentials(req);
+    let is_merge_patch = req.content_type() == constant::CONTENT_TYPE_MERGE_PATCH
+        || req.content_type() == constant::CONTENT_TYPE_JSON;
 
-    call_ctrl!(|| req
-        .json()
-        .from_err()
-        .and_then(move |updated_paste: UpdatePaste| db_chan
-            .send(paste_srv::UpdatePasteMsg {
-                id: updated_paste.id,
-                title: updated_paste.title,
-                body: updated_paste.body,
-                modified_at: SystemTime::now(),
-            }).map_err(ServerError::MailBox)
-            .from_err()))
+    call_ctrl!(
+        || req
+            .body()
+            .limit(constant::MAX_PASTE_SIZE)
+            .from_err()
+            .and_then(move |body| -> Result<_, UserError> {
+                if !is_merge_patch {
+                    return Err(UserError::Custom {
+                        code: StatusCode::UNSUPPORTED_MEDIA_TYPE.as_u16(),
+                        msg: constant::ERR_MSG_PAYLOAD_BAD_PATCH_TYPE.to_owned(),
+                    });
+                }
+
+                let patch = serde_json::from_slice::<PastePatch>(&body)
+                    .map_err(|err| UserError::PayloadError(err.to_string()))?;
+                let required = |field: Option<Option<String>>| match field {
+                    Some(None) => Err(UserError::PayloadError(
+                        constant::ERR_MSG_PAYLOAD_PATCH_REMOVES_REQUIRED.to_owned(),
+                    )),
+                    Some(value) => Ok(value),
+                    None => Ok(None),
+                };
+
+                Ok(paste_srv::UpdatePasteMsg {
+                    slug: slug?,
+                    title: required(patch.title)?,
+                    body: required(patch.body)?,
+                    language: match patch.language {
+                        Some(language) => Some(parse_language(language)?),
+                        None => None,
+                    },
+                    modified_at: SystemTime::now(),
+                    versions,
+                    credentials,
+                })
+            }).and_then(move |msg| db_chan.send(msg).map_err(ServerError::MailBox).from_err()),
+        |paste: Paste| HttpResponse::Ok().header(header::ETAG, etag(&paste)).json(paste)
+    )
 }
 
 pub fn del_paste_by_id(req: &HttpRequest<State>) -> FutureJsonResponse {
     let db_chan = req.state().db_chan.clone();
+    let versions = parse_if_match(req);
+    let credentials = credentials(req);
 
     call_ctrl!(|| future::ok(req.clone())
-        .and_then(|req| req.match_info()["id"].parse::<i64>())
-        .from_err()
-        .and_then(move |id| db_chan
-            .send(paste_srv::DelPasteByIdMsg { id })
+        .and_then(|req| parse_slug(&req))
+        .and_then(move |slug| db_chan
+            .send(paste_srv::DelPasteByIdMsg {
+                slug,
+                versions,
+                credentials,
+            })
             .map_err(ServerError::MailBox)
             .from_err()).map(|res| res.map(|_| "ok")))
 }
 
-// format: "GT/EQ/LT/GE/LE,seconds_since_UNIX_EPOCH"
-fn parse_time_cond(cond_str: &str) -> Result<paste_srv::TimeCondition, UserError> {
-    use self::paste_srv::{CmpOp, TimeCondition};
-    use std::time::{Duration, UNIX_EPOCH};
+pub fn get_paste_revision_list(req: &HttpRequest<State>) -> FutureJsonResponse {
+    let db_chan = req.state().db_chan.clone();
+    let credentials = credentials(req);
 
-    let default_err = Err(UserError::PayloadError(
-        constant::ERR_MSG_PAYLOAD_PARSE_TIME_COND_FAIL.to_owned(),
-    ));
-    let op_secs: Vec<&str> = cond_str.split(',').collect();
-    if op_secs.len() != 2 {
-        return default_err;
-    }
+    call_ctrl!(|| future::ok(req.clone())
+        .and_then(|req| parse_slug(&req))
+        .and_then(move |slug| db_chan
+            .send(revision_srv::GetPasteRevisionListMsg { slug, credentials })
+            .map_err(ServerError::MailBox)
+            .from_err()))
+}
+
+pub fn get_paste_revision(req: &HttpRequest<State>) -> FutureJsonResponse {
+    let db_chan = req.state().db_chan.clone();
+    let credentials = credentials(req);
 
-    let op = match op_secs[0] {
-        "GT" => Ok(CmpOp::GT),
-        "EQ" => Ok(CmpOp::EQ),
-        "LT" => Ok(CmpOp::LT),
-        "GE" => Ok(CmpOp::GE),
-        "LE" => Ok(CmpOp::LE),
-        _ => Err(()),
+    call_ctrl!(|| future::ok(req.clone())
+        .and_then(|req| parse_slug(&req).and_then(|slug| Ok((slug, parse_revision(&req)?))))
+        .and_then(move |(slug, revision)| db_chan
+            .send(revision_srv::GetPasteRevisionMsg {
+                slug,
+                revision,
+                credentials,
+            })
+            .map_err(ServerError::MailBox)
+            .from_err()))
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct DiffRevisionsConds {
+    from: Option<i32>,
+    to: Option<i32>,
+}
+
+pub fn diff_paste_revisions(
+    (req, conds): (HttpRequest<State>, Query<DiffRevisionsConds>),
+) -> FutureJsonResponse {
+    let db_chan = req.state().db_chan.clone();
+    let credentials = credentials(&req);
+    let revisions = match (conds.from, conds.to) {
+        (Some(from), Some(to)) if from > 0 && to > 0 => Ok((from, to)),
+        _ => Err(UserError::PayloadError(
+            constant::ERR_MSG_PAYLOAD_BAD_DIFF_REVISIONS.to_owned(),
+        )),
     };
-    let secs = op_secs[1].parse::<u64>();
 
-    if let (Ok(op), Ok(secs)) = (op, secs) {
-        Ok(TimeCondition {
-            op,
-            time: UNIX_EPOCH + Duration::from_secs(secs),
+    call_ctrl!(|| future::result(parse_slug(&req))
+        .and_then(move |slug| revisions.map(|(from, to)| diff_srv::DiffRevisionsMsg {
+            slug,
+            from,
+            to,
+            credentials,
+        })).and_then(move |msg| db_chan.send(msg).map_err(ServerError::MailBox).from_err()))
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct DiffPastesConds {
+    a: Option<String>,
+    b: Option<String>,
+}
+
+pub fn diff_pastes(
+    (req, conds): (HttpRequest<State>, Query<DiffPastesConds>),
+) -> FutureJsonResponse {
+    let db_chan = req.state().db_chan.clone();
+    let credentials = credentials(&req);
+    let pastes = match (conds.a.to_owned(), conds.b.to_owned()) {
+        (Some(ref a), Some(ref b)) if !slug::is_valid(a) || !slug::is_valid(b) => {
+            Err(UserError::BadID)
+        }
+        (Some(a), Some(b)) => Ok(diff_srv::DiffPastesMsg { a, b, credentials }),
+        _ => Err(UserError::PayloadError(
+            constant::ERR_MSG_PAYLOAD_BAD_DIFF_PASTES.to_owned(),
+        )),
+    };
+
+    call_ctrl!(|| future::result(pastes)
+        .and_then(move |msg| db_chan.send(msg).map_err(ServerError::MailBox).from_err()))
+}
+
+// filename safe to put into a header, keeps the extension given in title
+fn raw_filename(paste: &Paste) -> String {
+    let mut filename = paste
+        .title
+        .trim()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        }).take(constant::MAX_RAW_FILENAME_LEN)
+        .collect::<String>();
+
+    if filename.trim_matches(|c| c == '_' || c == '.').is_empty() {
+        filename = paste.slug.to_owned();
+    }
+    if !filename.contains('.') {
+        filename.push_str(".txt");
+    }
+
+    filename
+}
+
+// strong entity tag of a paste, unique among its versions
+fn etag(paste: &Paste) -> String {
+    format!("\"{}\"", paste.version)
+}
+
+// entity tags listed in a conditional header, each of possibly several lines
+fn entity_tags(req: &HttpRequest<State>, name: header::HeaderName) -> Vec<String> {
+    req.headers()
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().to_owned())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// Versions `If-Match` lets an update or delete through, `None` for any. weak tags never
+/// match for strong comparison, so those are left out
+fn parse_if_match(req: &HttpRequest<State>) -> Option<Vec<i32>> {
+    let tags = entity_tags(req, header::IF_MATCH);
+    if tags.is_empty() || tags.iter().any(|tag| tag == "*") {
+        return None;
+    }
+
+    Some(
+        tags.iter()
+            .filter(|tag| tag.len() > 1 && tag.starts_with('"') && tag.ends_with('"'))
+            .filter_map(|tag| tag[1..tag.len() - 1].parse::<i32>().ok())
+            .collect(),
+    )
+}
+
+fn parse_slug(req: &HttpRequest<State>) -> Result<String, UserError> {
+    let slug = &req.match_info()["id"];
+
+    if slug::is_valid(slug) {
+        Ok(slug.to_owned())
+    } else {
+        Err(UserError::BadID)
+    }
+}
+
+fn parse_revision(req: &HttpRequest<State>) -> Result<i32, UserError> {
+    match req.match_info()["revision"].parse::<i32>() {
+        Ok(revision) if revision > 0 => Ok(revision),
+        _ => Err(UserError::BadID),
+    }
+}
+
+fn parse_custom_slug(custom_slug: Option<String>) -> Result<Option<String>, UserError> {
+    match custom_slug {
+        None => Ok(None),
+        Some(custom_slug) => slug::validate_custom(&custom_slug)
+            .map(|_| Some(custom_slug))
+            .map_err(|msg| UserError::PayloadError(msg.to_owned())),
+    }
+}
+
+fn parse_language(language: Option<String>) -> Result<Option<String>, UserError> {
+    match language {
+        Some(ref language) if !highlight::is_valid_language(language) => Err(
+            UserError::PayloadError(constant::ERR_MSG_PAYLOAD_BAD_LANGUAGE.to_owned()),
+        ),
+        language => Ok(language),
+    }
+}
+
+fn parse_password(password: Option<String>) -> Result<Option<String>, UserError> {
+    match password {
+        Some(ref password)
+            if password.is_empty() || password.chars().count() > constant::MAX_PASSWORD_LEN =>
+        {
+            Err(UserError::PayloadError(
+                constant::ERR_MSG_PAYLOAD_BAD_PASSWORD.to_owned(),
+            ))
+        }
+        password => Ok(password),
+    }
+}
+
+fn parse_encryption(
+    encrypted: bool,
+    cipher: Option<String>,
+    nonce: Option<String>,
+    body: &str,
+) -> Result<Option<paste_srv::ClientEncryption>, UserError> {
+    let bad_encryption =
+        || UserError::PayloadError(constant::ERR_MSG_PAYLOAD_BAD_ENCRYPTION.to_owned());
+
+    if !encrypted {
+        return match (cipher, nonce) {
+            (None, None) => Ok(None),
+            _ => Err(bad_encryption()),
+        };
+    }
+
+    let cipher = cipher.unwrap_or_else(|| constant::CIPHER_AES_GCM.to_owned());
+    let nonce = nonce.ok_or_else(bad_encryption)?;
+    let nonce_len = base64::decode(&nonce).map_err(|_| bad_encryption())?.len();
+    // gcm appends its tag, so even an empty plaintext has some ciphertext
+    let body_len = base64::decode(body).map_err(|_| bad_encryption())?.len();
+
+    if cipher != constant::CIPHER_AES_GCM
+        || nonce_len != constant::AES_GCM_NONCE_LEN
+        || body_len < constant::AES_GCM_TAG_LEN
+    {
+        return Err(bad_encryption());
+    }
+
+    Ok(Some(paste_srv::ClientEncryption { cipher, nonce }))
+}
+
+fn parse_ttl(created_at: SystemTime, ttl: Option<u64>) -> Result<Option<SystemTime>, UserError> {
+    use std::time::Duration;
+
+    match ttl {
+        None => Ok(None),
+        Some(secs) if secs > 0 && secs <= constant::MAX_PASTE_TTL_SECS => {
+            Ok(Some(created_at + Duration::from_secs(secs)))
+        }
+        Some(_) => Err(UserError::PayloadError(
+            constant::ERR_MSG_PAYLOAD_BAD_TTL.to_owned(),
+        )),
+    }
+}
+
+// format: "GT/EQ/LT/GE/LE,TIME" or a closed range "TIME..TIME", where TIME is anything
+// `time::parse` takes
+fn parse_time_cond(name: &str, cond_str: &str) -> Result<TimeCondition, UserError> {
+    use crate::models::list::CmpOp;
+
+    let fail = |reason: String| {
+        UserError::PayloadError(format!(
+            "{}: {}: {}",
+            constant::ERR_MSG_PAYLOAD_PARSE_TIME_COND_FAIL,
+            name,
+            reason
+        ))
+    };
+    let parse_time = |part: &str, time_str: &str| {
+        time::parse(time_str).ok_or_else(|| {
+            fail(format!(
+                "invalid {} `{}`, {}",
+                part,
+                time_str,
+                constant::ERR_MSG_PAYLOAD_BAD_TIME
+            ))
         })
+    };
+
+    if let Some(idx) = cond_str.find("..") {
+        let start = parse_time("range start", &cond_str[..idx])?;
+        let end = parse_time("range end", &cond_str[idx + 2..])?;
+
+        if start > end {
+            return Err(fail("range start is after its end".to_owned()));
+        }
+        return Ok(TimeCondition::Between(start, end));
+    }
+
+    let mut op_time = cond_str.splitn(2, ',');
+    let (op_str, time_str) = match (op_time.next(), op_time.next()) {
+        (Some(op_str), Some(time_str)) => (op_str, time_str),
+        _ => return Err(fail("expected `OP,TIME` or `START..END`".to_owned())),
+    };
+
+    let op = match op_str {
+        "GT" => CmpOp::GT,
+        "EQ" => CmpOp::EQ,
+        "LT" => CmpOp::LT,
+        "GE" => CmpOp::GE,
+        "LE" => CmpOp::LE,
+        _ => {
+            return Err(fail(format!(
+                "unknown operator `{}`, expected one of GT, EQ, LT, GE or LE",
+                op_str
+            )))
+        }
+    };
+
+    Ok(TimeCondition::Cmp(op, parse_time("time", time_str)?))
+}
+
+fn parse_match_mode(mode: Option<&String>) -> Result<MatchMode, UserError> {
+    match mode.map(String::as_str) {
+        None | Some("prefix") => Ok(MatchMode::Prefix),
+        Some("contains") => Ok(MatchMode::Contains),
+        Some("exact") => Ok(MatchMode::Exact),
+        Some("regex") => Ok(MatchMode::Regex),
+        Some("fuzzy") => Ok(MatchMode::Fuzzy),
+        Some(_) => Err(UserError::PayloadError(
+            constant::ERR_MSG_PAYLOAD_BAD_MATCH_MODE.to_owned(),
+        )),
+    }
+}
+
+fn parse_filter(filter_str: &str) -> Result<Filter, UserError> {
+    filter::parse(filter_str).map_err(|err| {
+        UserError::PayloadError(format!("{}: {}", constant::ERR_MSG_PAYLOAD_BAD_FILTER, err))
+    })
+}
+
+fn parse_cursor(cursor: &str) -> Result<Cursor, UserError> {
+    if cursor.is_empty() {
+        return Ok(Cursor::default());
+    }
+
+    Cursor::decode(cursor)
+        .ok_or_else(|| UserError::PayloadError(constant::ERR_MSG_PAYLOAD_BAD_CURSOR.to_owned()))
+}
+
+fn check_paging(limit: Option<i64>, offset: Option<i64>) -> Result<(), UserError> {
+    let bad_limit = limit.map_or(false, |limit| limit < 1 || limit > constant::MAX_PAGE_SIZE);
+    let bad_offset = offset.map_or(false, |offset| offset < 0);
+
+    if bad_limit || bad_offset {
+        Err(UserError::PayloadError(constant::ERR_MSG_PAYLOAD_BAD_PAGING.to_owned()))
     } else {
-        default_err
+        Ok(())
+    }
+}
+
+fn check_cursor(list_conds: PasteListConds) -> Result<PasteListConds, UserError> {
+    let err = |reason: &str| Err(UserError::PayloadError(reason.to_owned()));
+
+    if list_conds.cursor.is_none() {
+        return Ok(list_conds);
+    }
+    if list_conds.q.is_some() {
+        return err(constant::ERR_MSG_PAYLOAD_CURSOR_WITH_QUERY);
+    }
+    if list_conds.offset.is_some() {
+        return err(constant::ERR_MSG_PAYLOAD_CURSOR_WITH_OFFSET);
     }
+
+    // a cursor only makes sense for the ordering it was taken from
+    if let Some(Cursor {
+        position: Some(ref position),
+        ..
+    }) = list_conds.cursor
+    {
+        let orderby_list = list_conds.orderby_list.as_ref().map_or(&[][..], Vec::as_slice);
+        let matches = position.keys.len() == orderby_list.len()
+            && position
+                .keys
+                .iter()
+                .zip(orderby_list)
+                .all(|(key, orderby)| key.is_for(&orderby.item));
+
+        if !matches {
+            return err(constant::ERR_MSG_PAYLOAD_BAD_CURSOR);
+        }
+    }
+
+    Ok(list_conds)
+}
+
+fn check_relevance_orderby(list_conds: PasteListConds) -> Result<PasteListConds, UserError> {
+    use crate::models::list::Item;
+
+    let by_relevance = list_conds.orderby_list.as_ref().map_or(false, |orderby_list| {
+        orderby_list.iter().any(|orderby| match orderby.item {
+            Item::Relevance => true,
+            _ => false,
+        })
+    });
+
+    if by_relevance && list_conds.q.is_none() {
+        return Err(UserError::PayloadError(
+            constant::ERR_MSG_PAYLOAD_RELEVANCE_WITHOUT_QUERY.to_owned(),
+        ));
+    }
+
+    Ok(list_conds)
 }
 
-// format: "Title/Body/CreatedAt/ModifiedAt:asc/decs"
-fn parse_orderby(orderby_str: &str) -> Result<Vec<paste_srv::Orderby>, UserError> {
-    use self::paste_srv::{Item, Order, Orderby};
+// format: "Title/Body/CreatedAt/ModifiedAt/Relevance:asc/decs"
+fn parse_orderby(orderby_str: &str) -> Result<Vec<Orderby>, UserError> {
+    use crate::models::list::{Item, Order};
 
     let default_err = Err(UserError::PayloadError(
         constant::ERR_MSG_PAYLOAD_PARSE_ORDERBY_FAIL.to_owned(),
@@ -195,6 +1007,7 @@ fn parse_orderby(orderby_str: &str) -> Result<Vec<paste_srv::Orderby>, UserError
             "Body" => Ok(Item::Body),
             "CreatedAt" => Ok(Item::CreatedAt),
             "ModifiedAt" => Ok(Item::ModifiedAt),
+            "Relevance" => Ok(Item::Relevance),
             _ => Err(()),
         };
         let order = match item_order[1] {
@@ -16,17 +16,82 @@ mod controllers;
 mod models;
 mod server;
 mod services;
+mod stores;
+mod tcp;
 #[cfg(test)]
 mod tests;
 
+use std::env;
 use std::process::exit;
 
-use crate::common::{config::Config, constant::CONFIG_FILENAME};
+use crate::common::{config::Config, constant::CONFIG_FILENAME, crypto::Keyring};
 use crate::server::Server;
+use crate::services::{paste::issue_edit_token, rotation};
 
 fn main() {
     let config = Config::load(CONFIG_FILENAME);
+
+    // `pastebin-actix rotate-keys` reseals stored pastes instead of serving them
+    if env::args().nth(1).map_or(false, |command| command == "rotate-keys") {
+        exit(rotate_keys(&config));
+    }
+    // `pastebin-actix issue-edit-token <id>` prints a new edit token for a paste
+    if env::args().nth(1).map_or(false, |command| command == "issue-edit-token") {
+        exit(issue_token(&config, env::args().nth(2)));
+    }
+
     let server = Server::new(&config).unwrap();
 
     exit(server.start());
 }
+
+fn rotate_keys(config: &Config) -> i32 {
+    let encryption = match config.encryption {
+        Some(ref encryption) => encryption,
+        None => {
+            eprintln!("no [encryption] section in config");
+            return 1;
+        }
+    };
+    let keyring = match Keyring::load(encryption) {
+        Ok(keyring) => keyring,
+        Err(err) => {
+            eprintln!("fail to load master keys: {}", err);
+            return 1;
+        }
+    };
+
+    let store = stores::open(config);
+    match rotation::rotate_keys(&*store, &keyring, encryption.batch_size) {
+        Ok(resealed) => {
+            println!("resealed {} pastes with key {}", resealed, keyring.active_id());
+            0
+        }
+        Err(err) => {
+            eprintln!("fail to rotate keys: {}", err);
+            1
+        }
+    }
+}
+
+fn issue_token(config: &Config, slug: Option<String>) -> i32 {
+    let slug = match slug {
+        Some(slug) => slug,
+        None => {
+            eprintln!("usage: pastebin-actix issue-edit-token <id>");
+            return 1;
+        }
+    };
+
+    let store = stores::open(config);
+    match issue_edit_token(&*store, &slug) {
+        Ok(edit_token) => {
+            println!("{}", edit_token);
+            0
+        }
+        Err(err) => {
+            eprintln!("fail to issue an edit token for {}: {}", slug, err);
+            1
+        }
+    }
+}
@@ -1,10 +1,18 @@
+use std::sync::Arc;
+
 use actix::prelude::*;
-use diesel::{
-    pg::PgConnection,
-    r2d2::{ConnectionManager, Pool},
-};
 
-pub struct DatabaseExecutor(pub Pool<ConnectionManager<PgConnection>>);
+use crate::common::crypto::Keyring;
+use crate::stores::PasteStore;
+
+/// Runs store calls off the event loop, sealing titles and bodies at rest if given keys
+pub struct DatabaseExecutor(pub Arc<PasteStore>, pub Option<Arc<Keyring>>);
+
+impl DatabaseExecutor {
+    pub fn keyring(&self) -> Option<&Keyring> {
+        self.1.as_ref().map(|keyring| &**keyring)
+    }
+}
 
 impl Actor for DatabaseExecutor {
     type Context = SyncContext<Self>;
@@ -0,0 +1,211 @@
+use std::time::SystemTime;
+
+use crate::common::{constant::DEFAULT_PAGE_LIMIT, filter::Filter};
+use crate::models::paste::Paste;
+
+#[derive(Debug)]
+pub enum Item {
+    Title,
+    Body,
+    CreatedAt,
+    ModifiedAt,
+    // rank of full-text search matches
+    Relevance,
+}
+
+#[derive(Debug)]
+pub enum Order {
+    Ascend,
+    Decrease,
+}
+
+#[derive(Debug)]
+pub enum CmpOp {
+    GT,
+    EQ,
+    LT,
+    GE,
+    LE,
+}
+
+// how `title_pat` and `body_pat` are matched against pastes
+#[derive(Debug)]
+pub enum MatchMode {
+    Prefix,
+    Contains,
+    Exact,
+    Regex,
+    // trigram similarity above `FUZZY_MATCH_THRESHOLD`
+    Fuzzy,
+}
+
+#[derive(Debug)]
+pub struct Orderby {
+    pub item: Item,
+    pub order: Order,
+}
+
+#[derive(Debug)]
+pub enum TimeCondition {
+    Cmp(CmpOp, SystemTime),
+    // closed range, both ends included
+    Between(SystemTime, SystemTime),
+}
+
+/// Value of a sort key of the row a cursor points at
+#[derive(Debug, Serialize, Deserialize)]
+pub enum SortKey {
+    Title(String),
+    Body(String),
+    CreatedAt(SystemTime),
+    ModifiedAt(SystemTime),
+}
+
+impl SortKey {
+    fn of(paste: &Paste, item: &Item) -> Option<Self> {
+        match *item {
+            Item::Title => Some(SortKey::Title(paste.title.to_owned())),
+            Item::Body => Some(SortKey::Body(paste.body.to_owned())),
+            Item::CreatedAt => Some(SortKey::CreatedAt(paste.created_at)),
+            Item::ModifiedAt => Some(SortKey::ModifiedAt(paste.modified_at)),
+            // ranks only exist in searches, which are not paged by cursor
+            Item::Relevance => None,
+        }
+    }
+
+    pub fn is_for(&self, item: &Item) -> bool {
+        match (self, item) {
+            (SortKey::Title(_), Item::Title)
+            | (SortKey::Body(_), Item::Body)
+            | (SortKey::CreatedAt(_), Item::CreatedAt)
+            | (SortKey::ModifiedAt(_), Item::ModifiedAt) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Sort keys of a row, followed by its slug to break ties
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CursorPosition {
+    pub keys: Vec<SortKey>,
+    pub slug: String,
+}
+
+/// Keyset paging through a listing ordered by `orderby_list` and then slug
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cursor {
+    // walk towards the start of the listing, for previous pages
+    pub backward: bool,
+    // the page starts right after this row, or at the very start if there is none
+    pub position: Option<CursorPosition>,
+}
+
+impl Cursor {
+    pub fn at(paste: &Paste, orderby_list: &[Orderby], backward: bool) -> Self {
+        Cursor {
+            backward,
+            position: Some(CursorPosition {
+                keys: orderby_list
+                    .iter()
+                    .filter_map(|orderby| SortKey::of(paste, &orderby.item))
+                    .collect(),
+                slug: paste.slug.to_owned(),
+            }),
+        }
+    }
+
+    /// Order rows are walked in, which is the reverse of the listing for backward cursors
+    pub fn walk(&self, order: &Order) -> Order {
+        match (order, self.backward) {
+            (Order::Ascend, false) | (Order::Decrease, true) => Order::Ascend,
+            _ => Order::Decrease,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("cursor is always serializable");
+        base64::encode_config(&json, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(encoded: &str) -> Option<Self> {
+        base64::decode_config(encoded, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+    }
+}
+
+/// Which pastes a listing, search or count covers, in which order and what page of them
+pub struct PasteListConds {
+    // full-text search query, matches are ranked by relevance
+    pub q: Option<String>,
+    pub title_pat: Option<String>,
+    pub title_mode: MatchMode,
+    pub body_pat: Option<String>,
+    pub body_mode: MatchMode,
+    pub language: Option<String>,
+    pub created_at: Option<TimeCondition>,
+    pub modified_at: Option<TimeCondition>,
+    // on top of all the conditions above
+    pub filter: Option<Filter>,
+    pub orderby_list: Option<Vec<Orderby>>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    // page by cursor instead of offset
+    pub cursor: Option<Cursor>,
+    // also count everything matching, regardless of paging
+    pub with_total: bool,
+}
+
+impl Default for PasteListConds {
+    fn default() -> Self {
+        PasteListConds {
+            q: None,
+            title_pat: None,
+            title_mode: MatchMode::Prefix,
+            body_pat: None,
+            body_mode: MatchMode::Prefix,
+            language: None,
+            created_at: None,
+            modified_at: None,
+            filter: None,
+            orderby_list: None,
+            limit: Some(DEFAULT_PAGE_LIMIT),
+            offset: Some(0),
+            cursor: None,
+            with_total: false,
+        }
+    }
+}
+
+impl PasteListConds {
+    /// Whether bodies are matched or ordered by, which leaves out pastes protected by a
+    /// password as either would give their content away bit by bit, and pastes encrypted
+    /// in the client as there is nothing to match in ciphertext
+    pub fn touches_body(&self) -> bool {
+        self.q.is_some()
+            || self.body_pat.is_some()
+            || self.filter.as_ref().map_or(false, Filter::mentions_body)
+            || self.orders_by(|item| match item {
+                Item::Body => true,
+                _ => false,
+            })
+    }
+
+    /// Whether titles or bodies are matched or ordered by, which cannot be done in the
+    /// database while they are sealed at rest
+    pub fn touches_text(&self) -> bool {
+        self.touches_body()
+            || self.title_pat.is_some()
+            || self.filter.as_ref().map_or(false, Filter::mentions_title)
+            || self.orders_by(|item| match item {
+                Item::Title => true,
+                _ => false,
+            })
+    }
+
+    fn orders_by(&self, is_item: fn(&Item) -> bool) -> bool {
+        self.orderby_list.as_ref().map_or(false, |orderby_list| {
+            orderby_list.iter().any(|orderby| is_item(&orderby.item))
+        })
+    }
+}
@@ -1,3 +1,5 @@
 pub mod executor;
+pub mod list;
 pub mod paste;
+pub mod revision;
 pub mod schema;
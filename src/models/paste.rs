@@ -1,14 +1,192 @@
+use std::io::Write;
 use std::time::SystemTime;
 
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    serialize::{self, Output, ToSql},
+    sql_types::Text,
+};
+use serde::{Deserialize, Deserializer, Serializer};
+
 use crate::models::schema::pastes;
 
-#[derive(Queryable, Debug, PartialEq, Serialize, Deserialize)]
+/// Who gets to see a paste, stored by its lowercase name
+#[derive(AsExpression, FromSqlRow, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[sql_type = "Text"]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    // listed for everyone
+    Public,
+    // never listed, reachable by its link
+    Unlisted,
+    // only reachable with the token handed out on creation
+    Private,
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility::Public
+    }
+}
+
+impl Visibility {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Visibility::Public => "public",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Private => "private",
+        }
+    }
+}
+
+impl<DB: Backend> ToSql<Text, DB> for Visibility
+where
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        self.as_str().to_sql(out)
+    }
+}
+
+impl<DB: Backend> FromSql<Text, DB> for Visibility
+where
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "public" => Ok(Visibility::Public),
+            "unlisted" => Ok(Visibility::Unlisted),
+            "private" => Ok(Visibility::Private),
+            other => Err(format!("unknown visibility: {}", other).into()),
+        }
+    }
+}
+
+#[derive(Queryable, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Paste {
+    // internal only, pastes are addressed by their slug
+    #[serde(skip)]
     pub id: i64,
     pub title: String,
     pub body: String,
     pub created_at: SystemTime,
     pub modified_at: SystemTime,
+    pub expires_at: Option<SystemTime>,
+    pub burn_after_read: bool,
+    pub slug: String,
+    pub language: Option<String>,
+    // bumped by every update, sent as the ETag of the paste
+    pub version: i32,
+    // argon2 hash, only whether there is one is ever shown
+    #[serde(
+        rename = "protected",
+        default,
+        serialize_with = "serialize_protected",
+        deserialize_with = "deserialize_protected"
+    )]
+    pub password_hash: Option<String>,
+    // set for bodies encrypted in the client, which are base64 ciphertext then
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cipher: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    // master key wrapping `data_key`, which seals title and body at rest, both none for
+    // pastes stored in plaintext
+    #[serde(skip)]
+    pub key_id: Option<String>,
+    #[serde(skip)]
+    pub data_key: Option<String>,
+    #[serde(default)]
+    pub visibility: Visibility,
+    // sha-256 of the token private pastes are opened with
+    #[serde(skip)]
+    pub access_token_hash: Option<String>,
+    // sha-256 of the token updates and deletes take
+    #[serde(skip)]
+    pub edit_token_hash: Option<String>,
+}
+
+fn serialize_protected<S: Serializer>(
+    password_hash: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bool(password_hash.is_some())
+}
+
+// the hash itself never comes back, a protected paste gets an empty one
+fn deserialize_protected<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(if bool::deserialize(deserializer)? {
+        Some(String::new())
+    } else {
+        None
+    })
+}
+
+impl Paste {
+    pub fn is_protected(&self) -> bool {
+        self.password_hash.is_some()
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+}
+
+/// A paste just created, along with the tokens to change it and to open it if it is
+/// private, which are never shown again
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatedPaste {
+    #[serde(flatten)]
+    pub paste: Paste,
+    pub edit_token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+}
+
+/// A paste in a listing, searches also tell how relevant it is and where it matched
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListedPaste {
+    #[serde(flatten)]
+    pub paste: Paste,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<f32>,
+    // html, escaped but for `<mark>` tags around the matches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+impl From<Paste> for ListedPaste {
+    fn from(paste: Paste) -> Self {
+        ListedPaste {
+            paste,
+            rank: None,
+            snippet: None,
+        }
+    }
+}
+
+/// A page of a listing, cursors are only given when paging by cursor
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PastePage {
+    pub items: Vec<ListedPaste>,
+    // number of pastes on all pages, only counted on request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
+
+/// A page of a listing by offset along with where it is among all pages
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasteListEnvelope {
+    pub items: Vec<ListedPaste>,
+    pub total: i64,
+    pub limit: Option<i64>,
+    pub offset: i64,
+    pub has_more: bool,
 }
 
 #[derive(Insertable)]
@@ -18,4 +196,26 @@ pub struct NewPaste<'a> {
     pub body: &'a str,
     pub created_at: &'a SystemTime,
     pub modified_at: &'a SystemTime,
+    pub expires_at: Option<&'a SystemTime>,
+    pub burn_after_read: bool,
+    pub slug: &'a str,
+    pub language: Option<&'a str>,
+    pub password_hash: Option<&'a str>,
+    pub cipher: Option<&'a str>,
+    pub nonce: Option<&'a str>,
+    pub key_id: Option<&'a str>,
+    pub data_key: Option<&'a str>,
+    pub visibility: Visibility,
+    pub access_token_hash: Option<&'a str>,
+    pub edit_token_hash: Option<&'a str>,
+}
+
+#[derive(AsChangeset)]
+#[table_name = "pastes"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct PasteChangeset<'a> {
+    pub title: &'a str,
+    pub body: &'a str,
+    pub language: Option<&'a str>,
+    pub modified_at: &'a SystemTime,
 }
@@ -0,0 +1,25 @@
+use std::time::SystemTime;
+
+use crate::models::schema::paste_revisions;
+
+#[derive(Queryable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PasteRevision {
+    #[serde(skip)]
+    pub id: i64,
+    #[serde(skip)]
+    pub paste_id: i64,
+    pub revision: i32,
+    pub title: String,
+    pub body: String,
+    pub created_at: SystemTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "paste_revisions"]
+pub struct NewPasteRevision<'a> {
+    pub paste_id: i64,
+    pub revision: i32,
+    pub title: &'a str,
+    pub body: &'a str,
+    pub created_at: &'a SystemTime,
+}
@@ -1,9 +1,38 @@
+// the generated `search_vector` tsvector column is left out, it is only used in raw sql
 table! {
     pastes (id) {
         id -> Int8,
-        title -> Varchar,
+        title -> Text,
         body -> Text,
         created_at -> Timestamp,
         modified_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+        burn_after_read -> Bool,
+        slug -> Varchar,
+        language -> Nullable<Varchar>,
+        version -> Int4,
+        password_hash -> Nullable<Varchar>,
+        cipher -> Nullable<Varchar>,
+        nonce -> Nullable<Varchar>,
+        key_id -> Nullable<Varchar>,
+        data_key -> Nullable<Varchar>,
+        visibility -> Varchar,
+        access_token_hash -> Nullable<Varchar>,
+        edit_token_hash -> Nullable<Varchar>,
     }
 }
+
+table! {
+    paste_revisions (id) {
+        id -> Int8,
+        paste_id -> Int8,
+        revision -> Int4,
+        title -> Text,
+        body -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+joinable!(paste_revisions -> pastes (paste_id));
+
+allow_tables_to_appear_in_same_query!(pastes, paste_revisions);
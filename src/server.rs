@@ -1,17 +1,19 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use failure::Error;
 
 use actix::{prelude::*, SystemRunner};
 use actix_web::server;
-use diesel::{
-    pg::PgConnection,
-    r2d2::{ConnectionManager, Pool},
-};
 
-use crate::common::config::Config;
+use crate::common::{config::Config, crypto::Keyring};
 use crate::models::executor::DatabaseExecutor;
+use crate::services::reaper::Reaper;
+use crate::stores;
 
 pub struct State {
     pub db_chan: Addr<DatabaseExecutor>,
+    pub slug_length: usize,
 }
 
 pub struct Server {
@@ -21,27 +23,39 @@ pub struct Server {
 impl Server {
     /// Create a new server instance
     pub fn new(config: &Config) -> Result<Self, Error> {
-        let database_url = format!(
-            "postgres://{}:{}@{}/{}",
-            config.postgres.username,
-            config.postgres.password,
-            config.postgres.host,
-            config.postgres.database,
-        );
-
         let runner = actix::System::new("pastebin-actix");
 
-        let manager = ConnectionManager::<PgConnection>::new(database_url);
-        let pool = Pool::builder()
-            .build(manager)
-            .expect("cannot build database connection pool");
+        let store = stores::open(config);
+        let keyring = match config.encryption {
+            Some(ref encryption) => Some(Arc::new(Keyring::load(encryption)?)),
+            None => None,
+        };
         let addr = SyncArbiter::start(config.actix.connections, move || {
-            DatabaseExecutor(pool.clone())
+            DatabaseExecutor(store.clone(), keyring.clone())
         });
 
+        Reaper::new(
+            addr.clone(),
+            Duration::from_secs(config.reaper.interval),
+            config.reaper.batch_size,
+        ).start();
+
+        let slug_length = config.paste.slug_length;
+        if let Some(ref tcp_config) = config.tcp {
+            let paste_url_base = match tcp_config.url {
+                Some(ref url) => format!("{}/pastes/", url.trim_right_matches('/')),
+                None => format!(
+                    "http://{}:{}/pastes/",
+                    config.server.ip, config.server.port
+                ),
+            };
+            crate::tcp::listen(tcp_config, addr.clone(), slug_length, paste_url_base)?;
+        }
+
         let server = server::new(move || {
             crate::apps::paste::create(State {
                 db_chan: addr.clone(),
+                slug_length,
             })
         });
         let server_url = format!("{}:{}", config.server.ip, config.server.port);
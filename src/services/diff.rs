@@ -0,0 +1,73 @@
+use actix::prelude::*;
+
+use crate::common::{
+    diff::{diff, Diff},
+    error::ServerError,
+};
+use crate::models::executor::DatabaseExecutor as DbExecutor;
+use crate::services::paste::{
+    check_access, data_key, unlock, unseal, unseal_revision, Credentials,
+};
+
+pub struct DiffRevisionsMsg {
+    pub slug: String,
+    pub from: i32,
+    pub to: i32,
+    pub credentials: Credentials,
+}
+
+impl Message for DiffRevisionsMsg {
+    type Result = Result<Diff, ServerError>;
+}
+
+impl Handler<DiffRevisionsMsg> for DbExecutor {
+    type Result = Result<Diff, ServerError>;
+
+    fn handle(&mut self, msg: DiffRevisionsMsg, _: &mut Self::Context) -> Self::Result {
+        let paste = unlock(self, &msg.slug, &msg.credentials)?;
+        // ciphertext from the client would only diff as noise, as in highlighting
+        if paste.is_encrypted() {
+            return Err(ServerError::Encrypted);
+        }
+        let data_key = data_key(self.keyring(), &paste)?;
+        let old = unseal_revision(data_key.as_ref(), self.0.get_revision(&msg.slug, msg.from)?)?;
+        let new = unseal_revision(data_key.as_ref(), self.0.get_revision(&msg.slug, msg.to)?)?;
+
+        diff(
+            &format!("{}@{}", msg.slug, old.revision),
+            &old.body,
+            &format!("{}@{}", msg.slug, new.revision),
+            &new.body,
+        )
+    }
+}
+
+pub struct DiffPastesMsg {
+    pub a: String,
+    pub b: String,
+    // have to give access to both
+    pub credentials: Credentials,
+}
+
+impl Message for DiffPastesMsg {
+    type Result = Result<Diff, ServerError>;
+}
+
+impl Handler<DiffPastesMsg> for DbExecutor {
+    type Result = Result<Diff, ServerError>;
+
+    fn handle(&mut self, msg: DiffPastesMsg, _: &mut Self::Context) -> Self::Result {
+        // diffing must not consume burn-after-read pastes, so they are never found here
+        let old = self.0.get_head(&msg.a)?;
+        let new = self.0.get_head(&msg.b)?;
+        check_access(&old, &msg.credentials)?;
+        check_access(&new, &msg.credentials)?;
+        if old.is_encrypted() || new.is_encrypted() {
+            return Err(ServerError::Encrypted);
+        }
+        let old = unseal(self.keyring(), old)?;
+        let new = unseal(self.keyring(), new)?;
+
+        diff(&old.slug, &old.body, &new.slug, &new.body)
+    }
+}
@@ -1 +1,5 @@
+pub mod diff;
 pub mod paste;
+pub mod reaper;
+pub mod revision;
+pub mod rotation;
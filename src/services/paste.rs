@@ -1,49 +1,276 @@
+use std::borrow::Cow;
 use std::time::SystemTime;
 
 use actix::prelude::*;
-use diesel::{self, prelude::*};
 
-use crate::common::error::ServerError;
+use crate::common::{
+    constant::{MAX_SLUG_ATTEMPTS, MAX_UPDATE_ATTEMPTS},
+    crypto::{DataKey, Keyring},
+    error::ServerError,
+    highlight::{detect_language, escape},
+    password,
+    slug::generate as generate_slug,
+    token,
+};
 use crate::models::{
     executor::DatabaseExecutor as DbExecutor,
-    paste::{NewPaste, Paste},
+    list::{Cursor, PasteListConds},
+    paste::{CreatedPaste, ListedPaste, NewPaste, Paste, PasteChangeset, PastePage, Visibility},
+    revision::PasteRevision,
 };
+use crate::stores::PasteStore;
+
+// sealed fields are bound to their names, so that a sealed title never opens as a body
+const TITLE: &str = "title";
+const BODY: &str = "body";
+
+/// Secrets a request comes with to get at pastes protected by a password or kept private,
+/// and to change them
+#[derive(Default)]
+pub struct Credentials {
+    pub password: Option<String>,
+    // handed out on creation of a private paste
+    pub token: Option<String>,
+    // handed out on creation of every paste
+    pub edit_token: Option<String>,
+}
+
+/// Whether `password` opens a paste, which always holds for pastes without one
+pub fn opens(paste: &Paste, password: Option<&str>) -> bool {
+    match (paste.password_hash.as_ref(), password) {
+        (None, _) => true,
+        (Some(hash), Some(password)) => password::verify(hash, password),
+        (Some(_), None) => false,
+    }
+}
+
+/// Whether `token` reveals a paste, which always holds for pastes other than private ones
+pub fn reveals(paste: &Paste, token: Option<&str>) -> bool {
+    match (paste.visibility, paste.access_token_hash.as_ref(), token) {
+        (Visibility::Private, Some(hash), Some(token)) => token::verify(hash, token),
+        (Visibility::Private, _, _) => false,
+        _ => true,
+    }
+}
+
+/// Check `credentials` let a paste be read, private pastes look missing to anyone without
+/// their token so that their slugs cannot be probed
+pub fn check_access(paste: &Paste, credentials: &Credentials) -> Result<(), ServerError> {
+    if !reveals(paste, credentials.token.as_ref().map(String::as_str)) {
+        return Err(ServerError::NotFound);
+    }
+
+    if opens(paste, credentials.password.as_ref().map(String::as_str)) {
+        Ok(())
+    } else {
+        Err(ServerError::WrongPassword)
+    }
+}
+
+/// Check `credentials` let a paste be changed, which takes its edit token, pastes stored
+/// before edit tokens were handed out have none and cannot be changed at all
+pub fn check_edit(paste: &Paste, credentials: &Credentials) -> Result<(), ServerError> {
+    match (paste.edit_token_hash.as_ref(), credentials.edit_token.as_ref()) {
+        (Some(hash), Some(edit_token)) if token::verify(hash, edit_token) => Ok(()),
+        _ => Err(ServerError::WrongEditToken),
+    }
+}
+
+/// Hand out a new edit token for a paste, the one it had stops working. Lets operators
+/// recover pastes stored before edit tokens, or whose token was lost
+pub fn issue_edit_token(store: &PasteStore, slug: &str) -> Result<String, ServerError> {
+    let edit_token = token::generate();
+    store.set_edit_token(slug, &token::hash(&edit_token))?;
+
+    Ok(edit_token)
+}
+
+/// Fetch a paste without consuming it, provided `credentials` give access to it
+pub fn unlock(
+    executor: &DbExecutor,
+    slug: &str,
+    credentials: &Credentials,
+) -> Result<Paste, ServerError> {
+    let paste = executor.0.peek(slug)?;
+
+    check_access(&paste, credentials)?;
+    unseal(executor.keyring(), paste)
+}
+
+/// Unwrap the data key sealing a paste at rest, none for pastes stored in plaintext
+pub fn data_key(keyring: Option<&Keyring>, paste: &Paste) -> Result<Option<DataKey>, ServerError> {
+    match (paste.key_id.as_ref(), paste.data_key.as_ref()) {
+        (Some(key_id), Some(wrapped)) => keyring
+            .ok_or(ServerError::Crypto("no master keys configured"))?
+            .unwrap(key_id, wrapped)
+            .map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Title and body as they are stored, sealed if there is a data key
+pub fn seal<'a>(
+    data_key: Option<&DataKey>,
+    title: &'a str,
+    body: &'a str,
+) -> Result<(Cow<'a, str>, Cow<'a, str>), ServerError> {
+    Ok(match data_key {
+        Some(data_key) => (
+            Cow::Owned(data_key.seal(TITLE, title)?),
+            Cow::Owned(data_key.seal(BODY, body)?),
+        ),
+        None => (Cow::Borrowed(title), Cow::Borrowed(body)),
+    })
+}
+
+/// Bring title and body of a stored paste back to plaintext
+pub fn unseal(keyring: Option<&Keyring>, mut paste: Paste) -> Result<Paste, ServerError> {
+    if let Some(data_key) = data_key(keyring, &paste)? {
+        paste.title = data_key.open(TITLE, &paste.title)?;
+        paste.body = data_key.open(BODY, &paste.body)?;
+    }
+
+    Ok(paste)
+}
+
+/// Same as `unseal` for a revision, which is sealed by the data key of its paste
+pub fn unseal_revision(
+    data_key: Option<&DataKey>,
+    mut revision: PasteRevision,
+) -> Result<PasteRevision, ServerError> {
+    if let Some(data_key) = data_key {
+        revision.title = data_key.open(TITLE, &revision.title)?;
+        revision.body = data_key.open(BODY, &revision.body)?;
+    }
+
+    Ok(revision)
+}
+
+/// How a body was encrypted in the client, which keeps the key to itself
+pub struct ClientEncryption {
+    pub cipher: String,
+    // base64, a fresh one for every paste
+    pub nonce: String,
+}
 
 pub struct CreatePasteMsg {
     pub title: String,
     pub body: String,
     pub created_at: SystemTime,
+    pub expires_at: Option<SystemTime>,
+    pub burn_after_read: bool,
+    // custom slug chosen by the creator, a random one is generated if omitted
+    pub slug: Option<String>,
+    pub slug_length: usize,
+    // detected from title and body if omitted
+    pub language: Option<String>,
+    // only its hash is kept
+    pub password: Option<String>,
+    // set when the body is ciphertext
+    pub encryption: Option<ClientEncryption>,
+    pub visibility: Visibility,
 }
 
 impl Message for CreatePasteMsg {
-    type Result = Result<Paste, ServerError>;
+    type Result = Result<CreatedPaste, ServerError>;
 }
 
 impl Handler<CreatePasteMsg> for DbExecutor {
-    type Result = Result<Paste, ServerError>;
+    type Result = Result<CreatedPaste, ServerError>;
 
     fn handle(&mut self, msg: CreatePasteMsg, _: &mut Self::Context) -> Self::Result {
-        use crate::models::schema::pastes::dsl::*;
-
-        let new_paste = NewPaste {
-            title: &msg.title,
-            body: &msg.body,
-            created_at: &msg.created_at,
-            modified_at: &msg.created_at,
+        // ciphertext tells nothing, only the title is left to guess from
+        let plaintext = match msg.encryption {
+            Some(_) => "",
+            None => &msg.body,
+        };
+        let paste_language = match msg.language {
+            Some(ref lang) => Some(lang.to_owned()),
+            None => detect_language(&msg.title, plaintext),
+        };
+        let password_hash = match msg.password {
+            Some(ref password) => {
+                Some(password::hash(password).map_err(ServerError::PasswordHash)?)
+            }
+            None => None,
         };
+        // private pastes are opened by a token only their creator is given
+        let access_token = match msg.visibility {
+            Visibility::Private => Some(token::generate()),
+            _ => None,
+        };
+        let access_token_hash = access_token.as_ref().map(String::as_str).map(token::hash);
+        // only whoever created the paste gets to change it
+        let edit_token = token::generate();
+        let edit_token_hash = token::hash(&edit_token);
+        // every paste gets a data key of its own, wrapped by the active master key
+        let keyring = self.keyring();
+        let sealing = match keyring {
+            Some(keyring) => Some(keyring.generate()?),
+            None => None,
+        };
+        let (title, body) = seal(
+            sealing.as_ref().map(|(data_key, _)| data_key),
+            &msg.title,
+            &msg.body,
+        )?;
+        let insert = |new_slug: &str| -> Result<Option<CreatedPaste>, ServerError> {
+            let created = self.0.create(&NewPaste {
+                title: &title,
+                body: &body,
+                created_at: &msg.created_at,
+                modified_at: &msg.created_at,
+                expires_at: msg.expires_at.as_ref(),
+                burn_after_read: msg.burn_after_read,
+                slug: new_slug,
+                language: paste_language.as_ref().map(String::as_str),
+                password_hash: password_hash.as_ref().map(String::as_str),
+                cipher: msg.encryption.as_ref().map(|enc| enc.cipher.as_str()),
+                nonce: msg.encryption.as_ref().map(|enc| enc.nonce.as_str()),
+                key_id: keyring.map(Keyring::active_id),
+                data_key: sealing.as_ref().map(|(_, wrapped)| wrapped.as_str()),
+                visibility: msg.visibility,
+                access_token_hash: access_token_hash.as_ref().map(String::as_str),
+                edit_token_hash: Some(edit_token_hash.as_str()),
+            })?;
+
+            match created {
+                Some(paste) => Ok(Some(CreatedPaste {
+                    paste: unseal(keyring, paste)?,
+                    edit_token: edit_token.clone(),
+                    access_token: access_token.clone(),
+                })),
+                None => Ok(None),
+            }
+        };
+
+        if let Some(ref custom_slug) = msg.slug {
+            return insert(custom_slug)?
+                .ok_or_else(|| ServerError::SlugTaken(custom_slug.to_owned()));
+        }
+
+        // retry with a fresh slug whenever the generated one is already taken
+        for _ in 0..MAX_SLUG_ATTEMPTS {
+            if let Some(created) = insert(&generate_slug(msg.slug_length))? {
+                return Ok(created);
+            }
+        }
 
-        diesel::insert_into(pastes)
-            .values(&new_paste)
-            .get_result(&self.0.get().map_err(ServerError::R2d2)?)
-            .map_err(ServerError::Database)
+        Err(ServerError::SlugExhausted)
     }
 }
 
+/// Changes to a paste, fields left as `None` keep their current value
 pub struct UpdatePasteMsg {
-    pub id: i64,
-    pub title: String,
-    pub body: String,
+    pub slug: String,
+    pub title: Option<String>,
+    pub body: Option<String>,
+    // `Some(None)` has it detected from title and body again
+    pub language: Option<Option<String>>,
     pub modified_at: SystemTime,
+    // versions the paste has to be at, from `If-Match`
+    pub versions: Option<Vec<i32>>,
+    pub credentials: Credentials,
 }
 
 impl Message for UpdatePasteMsg {
@@ -54,20 +281,63 @@ impl Handler<UpdatePasteMsg> for DbExecutor {
     type Result = Result<Paste, ServerError>;
 
     fn handle(&mut self, msg: UpdatePasteMsg, _: &mut Self::Context) -> Self::Result {
-        use crate::models::schema::pastes::dsl::*;
+        // without `If-Match` the fields left out are taken from the version just peeked, so
+        // the update is merged again whenever a concurrent one got in between
+        for _ in 0..MAX_UPDATE_ATTEMPTS {
+            match update_paste(self, &msg) {
+                Err(ServerError::VersionMismatch) if msg.versions.is_none() => continue,
+                res => return res,
+            }
+        }
 
-        diesel::update(pastes.find(msg.id))
-            .set((
-                title.eq(msg.title),
-                body.eq(msg.body),
-                modified_at.eq(msg.modified_at),
-            )).get_result(&self.0.get().map_err(ServerError::R2d2)?)
-            .map_err(ServerError::Database)
+        Err(ServerError::VersionMismatch)
     }
 }
 
+fn update_paste(executor: &DbExecutor, msg: &UpdatePasteMsg) -> Result<Paste, ServerError> {
+    let paste = unlock(executor, &msg.slug, &msg.credentials)?;
+    check_edit(&paste, &msg.credentials)?;
+    // pastes stored in plaintext stay so until `rotate-keys` seals them with history
+    let data_key = data_key(executor.keyring(), &paste)?;
+    let encrypted = paste.is_encrypted();
+    // new ciphertext would have to come with a new nonce, reusing one breaks aes-gcm
+    if encrypted && msg.body.as_ref().map_or(false, |body| *body != paste.body) {
+        return Err(ServerError::Encrypted);
+    }
+
+    // the version merged into, taken before the fields are moved out
+    let version = paste.version;
+    let versions = msg.versions.clone().unwrap_or_else(|| vec![version]);
+    // fields left out keep their current values
+    let title = msg.title.clone().unwrap_or(paste.title);
+    let body = msg.body.clone().unwrap_or(paste.body);
+    let language = msg.language.clone().unwrap_or(paste.language);
+    let paste_language = match language {
+        Some(lang) => Some(lang),
+        None if encrypted => detect_language(&title, ""),
+        None => detect_language(&title, &body),
+    };
+
+    let (title, body) = seal(data_key.as_ref(), &title, &body)?;
+
+    let updated = executor.0.update(
+        &msg.slug,
+        &PasteChangeset {
+            title: &title,
+            body: &body,
+            language: paste_language.as_ref().map(String::as_str),
+            modified_at: &msg.modified_at,
+        },
+        Some(versions.as_slice()),
+    )?;
+    unseal(executor.keyring(), updated)
+}
+
 pub struct GetPasteByIdMsg {
-    pub id: i64,
+    pub slug: String,
+    pub credentials: Credentials,
+    // the body is to be processed, which cannot be done with ciphertext
+    pub plaintext: bool,
 }
 
 impl Message for GetPasteByIdMsg {
@@ -78,156 +348,145 @@ impl Handler<GetPasteByIdMsg> for DbExecutor {
     type Result = Result<Paste, ServerError>;
 
     fn handle(&mut self, msg: GetPasteByIdMsg, _: &mut Self::Context) -> Self::Result {
-        use crate::models::schema::pastes::dsl::*;
-
-        pastes
-            .find(msg.id)
-            .get_result(&self.0.get().map_err(ServerError::R2d2)?)
-            .map_err(ServerError::Database)
-    }
-}
-
-#[derive(Debug)]
-pub enum Item {
-    Title,
-    Body,
-    CreatedAt,
-    ModifiedAt,
-}
-
-#[derive(Debug)]
-pub enum Order {
-    Ascend,
-    Decrease,
-}
-
-#[derive(Debug)]
-pub enum CmpOp {
-    GT,
-    EQ,
-    LT,
-    GE,
-    LE,
-}
-
-#[derive(Debug)]
-pub struct Orderby {
-    pub item: Item,
-    pub order: Order,
-}
-
-#[derive(Debug)]
-pub struct TimeCondition {
-    pub op: CmpOp,
-    pub time: SystemTime,
-}
-
-macro_rules! cmp {
-    ($query:expr, $column:expr, $cmp:expr, $cond:expr) => {
-        match $cmp {
-            CmpOp::GT => $query.filter($column.gt($cond)),
-            CmpOp::EQ => $query.filter($column.eq($cond)),
-            CmpOp::LT => $query.filter($column.lt($cond)),
-            CmpOp::GE => $query.filter($column.ge($cond)),
-            CmpOp::LE => $query.filter($column.le($cond)),
-        }
-    };
-}
-
-macro_rules! order {
-    ($query:expr, $column:expr, $order:expr) => {
-        match $order {
-            Order::Ascend => $query.order($column.asc()),
-            Order::Decrease => $query.order($column.desc()),
+        // checked ahead, wrong credentials must not burn a burn-after-read paste
+        let paste = unlock(self, &msg.slug, &msg.credentials)?;
+        if msg.plaintext && paste.is_encrypted() {
+            return Err(ServerError::Encrypted);
         }
-    };
-}
-
-macro_rules! orderby {
-    ($query:expr, $column:expr, $order:expr) => {
-        match $column {
-            Item::Title => order!($query, title, $order),
-            Item::Body => order!($query, body, $order),
-            Item::CreatedAt => order!($query, created_at, $order),
-            Item::ModifiedAt => order!($query, modified_at, $order),
-        }
-    };
-}
 
-pub struct GetPasteListMsg {
-    pub title_pat: Option<String>,
-    pub body_pat: Option<String>,
-    pub created_at: Option<TimeCondition>,
-    pub modified_at: Option<TimeCondition>,
-    pub orderby_list: Option<Vec<Orderby>>,
-    pub limit: Option<i64>,
-    pub offset: Option<i64>,
-}
-
-impl Default for GetPasteListMsg {
-    fn default() -> Self {
-        GetPasteListMsg {
-            title_pat: None,
-            body_pat: None,
-            created_at: None,
-            modified_at: None,
-            orderby_list: None,
-            limit: Some(20),
-            offset: Some(0),
+        if paste.burn_after_read {
+            unseal(self.keyring(), self.0.get(&msg.slug)?)
+        } else {
+            Ok(paste)
         }
     }
 }
 
+/// List pastes matching the conditions, a page of them when paging by cursor
+pub struct GetPasteListMsg(pub PasteListConds);
+
 impl Message for GetPasteListMsg {
-    type Result = Result<Vec<Paste>, ServerError>;
+    type Result = Result<PastePage, ServerError>;
 }
 
 impl Handler<GetPasteListMsg> for DbExecutor {
-    type Result = Result<Vec<Paste>, ServerError>;
+    type Result = Result<PastePage, ServerError>;
 
     fn handle(&mut self, msg: GetPasteListMsg, _: &mut Self::Context) -> Self::Result {
-        use crate::models::schema::pastes::dsl::*;
+        let mut conds = msg.0;
+        let keyring = self.keyring();
+        if keyring.is_some() && conds.touches_text() {
+            return Err(ServerError::Unsupported(
+                "matching or ordering by titles and bodies sealed at rest",
+            ));
+        }
 
-        let mut query = pastes.into_boxed();
+        let total = if conds.with_total {
+            Some(self.0.count(&conds)?)
+        } else {
+            None
+        };
 
-        if let Some(title_pat) = msg.title_pat {
-            query = query.filter(title.ilike(title_pat.to_owned() + "%"));
+        if conds.cursor.is_none() {
+            let items: Vec<ListedPaste> = match conds.q {
+                Some(ref q) => self
+                    .0
+                    .search(q, &conds)?
+                    .into_iter()
+                    .map(|mut item| {
+                        item.paste = unseal(keyring, item.paste)?;
+                        item.snippet = item.snippet.map(|snippet| escape_snippet(&snippet));
+                        Ok(item)
+                    }).collect::<Result<_, ServerError>>()?,
+                None => self
+                    .0
+                    .list(&conds)?
+                    .into_iter()
+                    .map(|paste| unseal(keyring, paste).map(list_item))
+                    .collect::<Result<_, _>>()?,
+            };
+
+            return Ok(PastePage {
+                items,
+                total,
+                next_cursor: None,
+                prev_cursor: None,
+            });
         }
 
-        if let Some(body_pat) = msg.body_pat {
-            query = query.filter(body.ilike(body_pat.to_owned() + "%"));
-        }
+        // one row more than a page tells whether there is anything beyond it
+        let limit = conds.limit;
+        conds.limit = limit.map(|limit| limit.saturating_add(1));
+        conds.offset = None;
 
-        if let Some(cond) = msg.created_at {
-            query = cmp!(query, created_at, cond.op, cond.time);
+        let mut pastes = self.0.list(&conds)?;
+        let beyond = limit.map_or(false, |limit| pastes.len() as i64 > limit);
+        if let Some(limit) = limit {
+            pastes.truncate(limit as usize);
         }
 
-        if let Some(cond) = msg.modified_at {
-            query = cmp!(query, modified_at, cond.op, cond.time);
-        }
+        let cursor = conds.cursor.unwrap_or_default();
+        let (has_next, has_prev) = if cursor.backward {
+            pastes.reverse();
+            (cursor.position.is_some(), beyond)
+        } else {
+            (beyond, cursor.position.is_some())
+        };
+        let orderby_list = conds.orderby_list.unwrap_or_default();
+        let next_cursor = pastes
+            .last()
+            .filter(|_| has_next)
+            .map(|paste| Cursor::at(paste, &orderby_list, false).encode());
+        let prev_cursor = pastes
+            .first()
+            .filter(|_| has_prev)
+            .map(|paste| Cursor::at(paste, &orderby_list, true).encode());
+
+        Ok(PastePage {
+            items: pastes
+                .into_iter()
+                .map(|paste| unseal(keyring, paste).map(list_item))
+                .collect::<Result<_, _>>()?,
+            total,
+            next_cursor,
+            prev_cursor,
+        })
+    }
+}
 
-        if let Some(orderby_list) = msg.orderby_list {
-            for orderby in orderby_list {
-                query = orderby!(query, orderby.item, orderby.order);
-            }
-        }
+// snippets mark matches with the tags stores put around them, to be shown as html, while
+// the text in between is straight from the body and has to be escaped
+fn escape_snippet(snippet: &str) -> String {
+    let mut escaped = String::with_capacity(snippet.len());
 
-        if let Some(limit) = msg.limit {
-            query = query.limit(limit);
+    for (i, part) in snippet.split("<mark>").enumerate() {
+        if i > 0 {
+            escaped.push_str("<mark>");
         }
-
-        if let Some(offset) = msg.offset {
-            query = query.offset(offset);
+        for (j, text) in part.split("</mark>").enumerate() {
+            if j > 0 {
+                escaped.push_str("</mark>");
+            }
+            escaped.push_str(&escape(text));
         }
+    }
 
-        query
-            .load::<Paste>(&self.0.get().map_err(ServerError::R2d2)?)
-            .map_err(ServerError::Database)
+    escaped
+}
+
+// listings show no more than the metadata of pastes protected by a password
+fn list_item(mut paste: Paste) -> ListedPaste {
+    if paste.is_protected() {
+        paste.body.clear();
     }
+
+    ListedPaste::from(paste)
 }
 
 pub struct DelPasteByIdMsg {
-    pub id: i64,
+    pub slug: String,
+    pub versions: Option<Vec<i32>>,
+    pub credentials: Credentials,
 }
 
 impl Message for DelPasteByIdMsg {
@@ -238,11 +497,29 @@ impl Handler<DelPasteByIdMsg> for DbExecutor {
     type Result = Result<usize, ServerError>;
 
     fn handle(&mut self, msg: DelPasteByIdMsg, _: &mut Self::Context) -> Self::Result {
-        use crate::models::schema::pastes::dsl::*;
+        let paste = self.0.peek(&msg.slug)?;
+        if !reveals(&paste, msg.credentials.token.as_ref().map(String::as_str)) {
+            return Err(ServerError::NotFound);
+        }
+        check_edit(&paste, &msg.credentials)?;
+
+        self.0.delete(&msg.slug, msg.versions.as_ref().map(Vec::as_slice))
+    }
+}
+
+pub struct PurgeExpiredPastesMsg {
+    pub now: SystemTime,
+    pub batch_size: i64,
+}
+
+impl Message for PurgeExpiredPastesMsg {
+    type Result = Result<usize, ServerError>;
+}
+
+impl Handler<PurgeExpiredPastesMsg> for DbExecutor {
+    type Result = Result<usize, ServerError>;
 
-        diesel::delete(pastes)
-            .filter(id.eq(msg.id))
-            .execute(&self.0.get().map_err(ServerError::R2d2)?)
-            .map_err(ServerError::Database)
+    fn handle(&mut self, msg: PurgeExpiredPastesMsg, _: &mut Self::Context) -> Self::Result {
+        self.0.purge_expired(msg.now, msg.batch_size)
     }
 }
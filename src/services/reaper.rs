@@ -0,0 +1,46 @@
+use std::time::{Duration, SystemTime};
+
+use actix::prelude::*;
+
+use crate::models::executor::DatabaseExecutor as DbExecutor;
+use crate::services::paste::PurgeExpiredPastesMsg;
+
+/// Periodically purges expired pastes through the database executor
+pub struct Reaper {
+    db_chan: Addr<DbExecutor>,
+    interval: Duration,
+    batch_size: i64,
+}
+
+impl Reaper {
+    pub fn new(db_chan: Addr<DbExecutor>, interval: Duration, batch_size: i64) -> Self {
+        Reaper {
+            db_chan,
+            interval,
+            batch_size,
+        }
+    }
+
+    fn purge(&mut self, ctx: &mut Context<Self>) {
+        // wait for the purge to finish so that runs never overlap
+        self.db_chan
+            .send(PurgeExpiredPastesMsg {
+                now: SystemTime::now(),
+                batch_size: self.batch_size,
+            }).into_actor(self)
+            .map(|res, _, _| {
+                if let Err(err) = res {
+                    eprintln!("fail to purge expired pastes: {}", err);
+                }
+            }).map_err(|err, _, _| eprintln!("fail to purge expired pastes: {}", err))
+            .wait(ctx);
+    }
+}
+
+impl Actor for Reaper {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(self.interval, |reaper, ctx| reaper.purge(ctx));
+    }
+}
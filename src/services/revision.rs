@@ -0,0 +1,50 @@
+use actix::prelude::*;
+
+use crate::common::error::ServerError;
+use crate::models::{executor::DatabaseExecutor as DbExecutor, revision::PasteRevision};
+use crate::services::paste::{data_key, unlock, unseal_revision, Credentials};
+
+pub struct GetPasteRevisionListMsg {
+    pub slug: String,
+    pub credentials: Credentials,
+}
+
+impl Message for GetPasteRevisionListMsg {
+    type Result = Result<Vec<PasteRevision>, ServerError>;
+}
+
+impl Handler<GetPasteRevisionListMsg> for DbExecutor {
+    type Result = Result<Vec<PasteRevision>, ServerError>;
+
+    fn handle(&mut self, msg: GetPasteRevisionListMsg, _: &mut Self::Context) -> Self::Result {
+        let paste = unlock(self, &msg.slug, &msg.credentials)?;
+        let data_key = data_key(self.keyring(), &paste)?;
+
+        self.0
+            .list_revisions(&msg.slug)?
+            .into_iter()
+            .map(|revision| unseal_revision(data_key.as_ref(), revision))
+            .collect()
+    }
+}
+
+pub struct GetPasteRevisionMsg {
+    pub slug: String,
+    pub revision: i32,
+    pub credentials: Credentials,
+}
+
+impl Message for GetPasteRevisionMsg {
+    type Result = Result<PasteRevision, ServerError>;
+}
+
+impl Handler<GetPasteRevisionMsg> for DbExecutor {
+    type Result = Result<PasteRevision, ServerError>;
+
+    fn handle(&mut self, msg: GetPasteRevisionMsg, _: &mut Self::Context) -> Self::Result {
+        let paste = unlock(self, &msg.slug, &msg.credentials)?;
+        let data_key = data_key(self.keyring(), &paste)?;
+
+        unseal_revision(data_key.as_ref(), self.0.get_revision(&msg.slug, msg.revision)?)
+    }
+}
@@ -0,0 +1,62 @@
+use crate::common::{crypto::Keyring, error::ServerError};
+use crate::models::{paste::Paste, revision::PasteRevision};
+use crate::services::paste::{data_key, seal, unseal, unseal_revision};
+use crate::stores::PasteStore;
+
+/// Seal every paste and its revisions with a fresh data key wrapped by the active master
+/// key, in batches of `batch_size`, pastes stored in plaintext included. Updates landing
+/// meanwhile would be overwritten, so this is run with the server stopped. Returns how
+/// many pastes were resealed
+pub fn rotate_keys(
+    store: &PasteStore,
+    keyring: &Keyring,
+    batch_size: i64,
+) -> Result<usize, ServerError> {
+    let mut resealed = 0;
+
+    loop {
+        // resealed pastes drop out, so every batch starts over from the first left
+        let batch = store.list_unsealed(keyring.active_id(), batch_size)?;
+        if batch.is_empty() {
+            break;
+        }
+
+        for (paste, revisions) in batch {
+            let old_key = data_key(Some(keyring), &paste)?;
+            let (new_key, wrapped) = keyring.generate()?;
+
+            let reseal = |title: &str, body: &str| -> Result<(String, String), ServerError> {
+                let (title, body) = seal(Some(&new_key), title, body)?;
+                Ok((title.into_owned(), body.into_owned()))
+            };
+
+            let revisions = revisions
+                .into_iter()
+                .map(|revision| {
+                    let revision = unseal_revision(old_key.as_ref(), revision)?;
+                    let (title, body) = reseal(&revision.title, &revision.body)?;
+                    Ok(PasteRevision {
+                        title,
+                        body,
+                        ..revision
+                    })
+                }).collect::<Result<Vec<_>, ServerError>>()?;
+
+            let paste = unseal(Some(keyring), paste)?;
+            let (title, body) = reseal(&paste.title, &paste.body)?;
+            store.reseal(
+                &Paste {
+                    title,
+                    body,
+                    key_id: Some(keyring.active_id().to_owned()),
+                    data_key: Some(wrapped),
+                    ..paste
+                },
+                &revisions,
+            )?;
+            resealed += 1;
+        }
+    }
+
+    Ok(resealed)
+}
@@ -0,0 +1,261 @@
+// rows whose expiration time has passed are treated as if they were gone
+macro_rules! unexpired {
+    ($now:expr) => {
+        expires_at.is_null().or(expires_at.gt($now))
+    };
+}
+
+macro_rules! cmp {
+    ($query:expr, $column:expr, $cmp:expr, $cond:expr) => {
+        match $cmp {
+            CmpOp::GT => $query.filter($column.gt($cond)),
+            CmpOp::EQ => $query.filter($column.eq($cond)),
+            CmpOp::LT => $query.filter($column.lt($cond)),
+            CmpOp::GE => $query.filter($column.ge($cond)),
+            CmpOp::LE => $query.filter($column.le($cond)),
+        }
+    };
+}
+
+// `$time` converts a SystemTime to what the backend keeps in time columns
+macro_rules! cmp_time {
+    ($query:expr, $column:expr, $cond:expr, $time:expr) => {
+        match *$cond {
+            TimeCondition::Cmp(ref op, time) => cmp!($query, $column, *op, $time(time)),
+            TimeCondition::Between(start, end) => {
+                $query.filter($column.between($time(start), $time(end)))
+            }
+        }
+    };
+}
+
+// comparison of a time column in a filter expression
+macro_rules! time_cond {
+    ($column:expr, $op:expr, $time:expr) => {
+        match $op {
+            TimeOp::Eq => Box::new($column.eq($time)),
+            TimeOp::Ne => Box::new($column.ne($time)),
+            TimeOp::Gt => Box::new($column.gt($time)),
+            TimeOp::Ge => Box::new($column.ge($time)),
+            TimeOp::Lt => Box::new($column.lt($time)),
+            TimeOp::Le => Box::new($column.le($time)),
+        }
+    };
+}
+
+macro_rules! paginate {
+    ($query:expr, $conds:expr) => {{
+        let mut query = $query;
+
+        if let Some(limit) = $conds.limit {
+            query = query.limit(limit);
+        }
+
+        if let Some(offset) = $conds.offset {
+            query = query.offset(offset);
+        }
+
+        query
+    }};
+}
+
+macro_rules! order {
+    ($query:expr, $column:expr, $order:expr) => {
+        match $order {
+            Order::Ascend => $query.then_order_by($column.asc()),
+            Order::Decrease => $query.then_order_by($column.desc()),
+        }
+    };
+}
+
+macro_rules! orderby {
+    ($query:expr, $column:expr, $order:expr) => {
+        match $column {
+            Item::Title => order!($query, title, $order),
+            Item::Body => order!($query, body, $order),
+            Item::CreatedAt => order!($query, created_at, $order),
+            Item::ModifiedAt => order!($query, modified_at, $order),
+            // there is nothing to rank outside of a full-text search
+            Item::Relevance => $query,
+        }
+    };
+    ($query:expr, $column:expr, $order:expr, $rank:expr) => {
+        match $column {
+            Item::Title => order!($query, title, $order),
+            Item::Body => order!($query, body, $order),
+            Item::CreatedAt => order!($query, created_at, $order),
+            Item::ModifiedAt => order!($query, modified_at, $order),
+            Item::Relevance => order!($query, $rank, $order),
+        }
+    };
+}
+
+// order of a cursor walk, slugs break ties so that every row has a distinct position
+macro_rules! cursor_order {
+    ($query:expr, $cursor:expr, $orderby_list:expr) => {{
+        let mut query = $query;
+
+        for orderby in $orderby_list {
+            query = orderby!(query, orderby.item, $cursor.walk(&orderby.order));
+        }
+
+        order!(query, slug, $cursor.walk(&Order::Ascend))
+    }};
+}
+
+macro_rules! key_after {
+    ($column:expr, $value:expr, $order:expr, $rest:expr) => {{
+        let value = $value;
+
+        match $order {
+            Order::Ascend => {
+                Box::new($column.gt(value.clone()).or($column.eq(value).and($rest)))
+            }
+            Order::Decrease => {
+                Box::new($column.lt(value.clone()).or($column.eq(value).and($rest)))
+            }
+        }
+    }};
+}
+
+// rows walked after the cursor position, that is
+// `k1 > v1 OR (k1 = v1 AND (k2 > v2 OR (k2 = v2 AND slug > s)))` for ascending keys
+macro_rules! after_position {
+    ($cursor:expr, $position:expr, $orderby_list:expr, $backend:ty, $time:expr) => {{
+        type Predicate = Box<BoxableExpression<pastes, $backend, SqlType = Bool>>;
+
+        let mut predicate: Predicate = match $cursor.walk(&Order::Ascend) {
+            Order::Ascend => Box::new(slug.gt($position.slug.to_owned())),
+            Order::Decrease => Box::new(slug.lt($position.slug.to_owned())),
+        };
+
+        for (orderby, key) in $orderby_list.iter().zip(&$position.keys).rev() {
+            let order = $cursor.walk(&orderby.order);
+
+            predicate = match *key {
+                SortKey::Title(ref value) => {
+                    key_after!(title, value.to_owned(), order, predicate)
+                }
+                SortKey::Body(ref value) => key_after!(body, value.to_owned(), order, predicate),
+                SortKey::CreatedAt(value) => {
+                    key_after!(created_at, $time(value), order, predicate)
+                }
+                SortKey::ModifiedAt(value) => {
+                    key_after!(modified_at, $time(value), order, predicate)
+                }
+            };
+        }
+
+        predicate
+    }};
+}
+
+// `compile_filter` and `compile_cond` turning filter expressions into `Predicate`s, `$time`
+// converts a SystemTime to what the backend keeps in time columns
+macro_rules! compile_filter {
+    ($time:expr) => {
+        /// Turn a filter expression into sql, `not` is pushed down to the conditions on the way
+        fn compile_filter(filter: &Filter, negated: bool) -> Predicate {
+            match (filter, negated) {
+                (Filter::And(a, b), false) | (Filter::Or(a, b), true) => {
+                    Box::new(compile_filter(a, negated).and(compile_filter(b, negated)))
+                }
+                (Filter::Or(a, b), false) | (Filter::And(a, b), true) => {
+                    Box::new(compile_filter(a, negated).or(compile_filter(b, negated)))
+                }
+                (Filter::Not(inner), _) => compile_filter(inner, !negated),
+                (Filter::Cond(cond), true) => compile_cond(&cond.negate()),
+                (Filter::Cond(cond), false) => compile_cond(cond),
+            }
+        }
+
+        fn compile_cond(cond: &Cond) -> Predicate {
+            use self::pastes::dsl::*;
+
+            match *cond {
+                Cond::Title(op, ref value) => text_cond!(title, op, value),
+                Cond::Body(op, ref value) => text_cond!(body, op, value),
+                Cond::LanguageIs(ref value) => Box::new(language.eq(value.to_owned())),
+                // `!=` alone would leave out pastes without a language
+                Cond::LanguageIsNot(ref value) => {
+                    Box::new(language.is_null().or(language.ne(value.to_owned())))
+                }
+                Cond::CreatedAt(op, time) => time_cond!(created_at, op, $time(time)),
+                Cond::ModifiedAt(op, time) => time_cond!(modified_at, op, $time(time)),
+            }
+        }
+    };
+}
+
+// `record_revision` for connections `$conn` to pastes read as `$paste`, which inserts
+// revisions as `$new_revision`
+macro_rules! record_revision {
+    ($conn:ty, $paste:ty, $new_revision:ident) => {
+        /// Append current content of given paste as its newest revision
+        fn record_revision(conn: &$conn, paste: &$paste) -> QueryResult<usize> {
+            use self::paste_revisions::dsl::*;
+            use diesel::dsl::max;
+
+            let latest = paste_revisions
+                .filter(paste_id.eq(paste.id))
+                .select(max(revision))
+                .first::<Option<i32>>(conn)?;
+
+            diesel::insert_into(paste_revisions)
+                .values(&$new_revision {
+                    paste_id: paste.id,
+                    revision: latest.unwrap_or(0) + 1,
+                    title: &paste.title,
+                    body: &paste.body,
+                    created_at: &paste.modified_at,
+                }).execute(conn)
+        }
+    };
+}
+
+// `find_head_id` for connections `$conn`, `$time` as in `compile_filter!`
+macro_rules! find_head_id {
+    ($conn:ty, $time:expr) => {
+        /// Find internal id of the paste owning revisions, which are only reachable while it is
+        fn find_head_id(conn: &$conn, paste_slug: &str) -> QueryResult<i64> {
+            use self::pastes::dsl::*;
+
+            let now = $time(SystemTime::now());
+
+            pastes
+                .select(id)
+                .filter(slug.eq(paste_slug))
+                .filter(unexpired!(now))
+                .filter(burn_after_read.eq(false))
+                .get_result(conn)
+        }
+    };
+}
+
+// fetch a paste read as `$paste`, burn-after-read pastes are deleted by the same
+// transaction, `$time` as in `compile_filter!`
+macro_rules! get_paste {
+    ($conn:expr, $paste_slug:expr, $paste:ty, $time:expr) => {{
+        use self::pastes::dsl::*;
+
+        let conn = $conn;
+        let now = $time(SystemTime::now());
+
+        conn.transaction::<_, DieselError, _>(|| {
+            let paste = pastes
+                .filter(slug.eq($paste_slug))
+                .filter(unexpired!(now))
+                .get_result::<$paste>(&conn)?;
+
+            if paste.burn_after_read {
+                // concurrent readers race on the delete, only the winner sees the paste
+                let deleted = diesel::delete(pastes.find(paste.id)).execute(&conn)?;
+                if deleted == 0 {
+                    return Err(DieselError::NotFound);
+                }
+            }
+
+            Ok(Paste::from(paste))
+        }).map_err(ServerError::Database)
+    }};
+}
@@ -0,0 +1,573 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use regex::RegexBuilder;
+
+use crate::common::{
+    constant::FUZZY_MATCH_THRESHOLD,
+    error::ServerError,
+    filter::{Cond, Filter, TextOp, TimeOp},
+};
+use crate::models::{
+    list::{
+        CmpOp, Cursor, CursorPosition, Item, MatchMode, Order, Orderby, PasteListConds, SortKey,
+        TimeCondition,
+    },
+    paste::{NewPaste, Paste, PasteChangeset, Visibility},
+    revision::PasteRevision,
+};
+use crate::stores::PasteStore;
+
+/// Keeps everything in process memory, for local development and tests
+#[derive(Default)]
+pub struct MemoryStore {
+    data: Mutex<MemoryData>,
+}
+
+#[derive(Default)]
+struct MemoryData {
+    last_id: i64,
+    pastes: Vec<Paste>,
+    revisions: Vec<PasteRevision>,
+}
+
+impl MemoryData {
+    fn find(&self, slug: &str, now: SystemTime) -> Option<usize> {
+        self.pastes
+            .iter()
+            .position(|paste| paste.slug == slug && is_unexpired(paste, now))
+    }
+
+    fn find_head(&self, slug: &str) -> Result<&Paste, ServerError> {
+        self.pastes
+            .iter()
+            .find(|paste| {
+                paste.slug == slug && is_unexpired(paste, SystemTime::now()) && !paste.burn_after_read
+            }).ok_or(ServerError::NotFound)
+    }
+
+    fn record_revision(&mut self, pos: usize) {
+        let paste = &self.pastes[pos];
+        let latest = self
+            .revisions
+            .iter()
+            .filter(|revision| revision.paste_id == paste.id)
+            .map(|revision| revision.revision)
+            .max();
+
+        self.last_id += 1;
+        let revision = PasteRevision {
+            id: self.last_id,
+            paste_id: paste.id,
+            revision: latest.unwrap_or(0) + 1,
+            title: paste.title.to_owned(),
+            body: paste.body.to_owned(),
+            created_at: paste.modified_at,
+        };
+        self.revisions.push(revision);
+    }
+
+    /// Listed pastes matching all conditions but the cursor, in insertion order
+    fn filter(&self, conds: &PasteListConds, now: SystemTime) -> Result<Vec<&Paste>, ServerError> {
+        let title_matcher = match conds.title_pat {
+            Some(ref pat) => Some(text_matcher(&conds.title_mode, pat)?),
+            None => None,
+        };
+        let body_matcher = match conds.body_pat {
+            Some(ref pat) => Some(text_matcher(&conds.body_mode, pat)?),
+            None => None,
+        };
+
+        let touches_body = conds.touches_body();
+
+        Ok(self
+            .pastes
+            .iter()
+            .filter(|paste| is_unexpired(paste, now) && !paste.burn_after_read)
+            .filter(|paste| paste.visibility == Visibility::Public)
+            .filter(|paste| !(touches_body && (paste.is_protected() || paste.is_encrypted())))
+            .filter(|paste| title_matcher.as_ref().map_or(true, |matches| matches(&paste.title)))
+            .filter(|paste| body_matcher.as_ref().map_or(true, |matches| matches(&paste.body)))
+            .filter(|paste| {
+                conds
+                    .language
+                    .as_ref()
+                    .map_or(true, |lang| paste.language.as_ref() == Some(lang))
+            }).filter(|paste| {
+                conds
+                    .created_at
+                    .as_ref()
+                    .map_or(true, |cond| time_matches(paste.created_at, cond))
+            }).filter(|paste| {
+                conds
+                    .modified_at
+                    .as_ref()
+                    .map_or(true, |cond| time_matches(paste.modified_at, cond))
+            }).filter(|paste| {
+                conds
+                    .filter
+                    .as_ref()
+                    .map_or(true, |filter| filter_matches(filter, paste))
+            }).collect())
+    }
+
+    fn remove_where<F: Fn(&Paste) -> bool>(&mut self, f: F) -> usize {
+        let (removed, kept): (Vec<_>, Vec<_>) = self.pastes.drain(..).partition(f);
+
+        self.pastes = kept;
+        // revisions go along with their paste, like ON DELETE CASCADE does
+        self.revisions
+            .retain(|revision| removed.iter().all(|paste| paste.id != revision.paste_id));
+
+        removed.len()
+    }
+}
+
+impl MemoryStore {
+    /// Start with given pastes and no revisions
+    #[cfg(test)]
+    pub fn with_pastes(pastes: Vec<Paste>) -> Self {
+        let last_id = pastes.iter().map(|paste| paste.id).max().unwrap_or(0);
+
+        MemoryStore {
+            data: Mutex::new(MemoryData {
+                last_id,
+                pastes,
+                revisions: vec![],
+            }),
+        }
+    }
+
+    fn data(&self) -> MutexGuard<MemoryData> {
+        // nothing is left half-updated by a panicking holder, so a poisoned lock is still usable
+        match self.data.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+fn is_unexpired(paste: &Paste, now: SystemTime) -> bool {
+    paste.expires_at.map_or(true, |expires_at| expires_at > now)
+}
+
+fn time_matches(time: SystemTime, cond: &TimeCondition) -> bool {
+    match *cond {
+        TimeCondition::Cmp(CmpOp::GT, other) => time > other,
+        TimeCondition::Cmp(CmpOp::EQ, other) => time == other,
+        TimeCondition::Cmp(CmpOp::LT, other) => time < other,
+        TimeCondition::Cmp(CmpOp::GE, other) => time >= other,
+        TimeCondition::Cmp(CmpOp::LE, other) => time <= other,
+        TimeCondition::Between(start, end) => start <= time && time <= end,
+    }
+}
+
+fn filter_matches(filter: &Filter, paste: &Paste) -> bool {
+    match *filter {
+        Filter::And(ref a, ref b) => filter_matches(a, paste) && filter_matches(b, paste),
+        Filter::Or(ref a, ref b) => filter_matches(a, paste) || filter_matches(b, paste),
+        Filter::Not(ref inner) => !filter_matches(inner, paste),
+        Filter::Cond(ref cond) => cond_matches(cond, paste),
+    }
+}
+
+fn cond_matches(cond: &Cond, paste: &Paste) -> bool {
+    let text_matches = |text: &str, op: TextOp, value: &str| match op {
+        TextOp::Eq => text == value,
+        TextOp::Ne => text != value,
+        TextOp::Contains => text.to_lowercase().contains(&value.to_lowercase()),
+        TextOp::NotContains => !text.to_lowercase().contains(&value.to_lowercase()),
+    };
+    let compare_time = |time: SystemTime, op: TimeOp, value: SystemTime| match op {
+        TimeOp::Eq => time == value,
+        TimeOp::Ne => time != value,
+        TimeOp::Gt => time > value,
+        TimeOp::Ge => time >= value,
+        TimeOp::Lt => time < value,
+        TimeOp::Le => time <= value,
+    };
+
+    match *cond {
+        Cond::Title(op, ref value) => text_matches(&paste.title, op, value),
+        Cond::Body(op, ref value) => text_matches(&paste.body, op, value),
+        Cond::LanguageIs(ref value) => paste.language.as_ref() == Some(value),
+        Cond::LanguageIsNot(ref value) => paste.language.as_ref() != Some(value),
+        Cond::CreatedAt(op, time) => compare_time(paste.created_at, op, time),
+        Cond::ModifiedAt(op, time) => compare_time(paste.modified_at, op, time),
+    }
+}
+
+/// Same as sql `ILIKE`, `%` matches any run of characters, `_` a single one
+/// and `\` escapes the next character
+fn ilike(text: &str, pattern: &str) -> bool {
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let (mut t, mut p) = (0, 0);
+    // where to resume when the characters after the last `%` stop matching
+    let mut backtrack = None;
+
+    while t < text.len() {
+        let escaped = p + 1 < pattern.len() && pattern[p] == '\\';
+
+        if escaped && pattern[p + 1] == text[t] {
+            t += 1;
+            p += 2;
+        } else if !escaped && p < pattern.len() && pattern[p] == '%' {
+            backtrack = Some((p + 1, t));
+            p += 1;
+        } else if !escaped
+            && p < pattern.len()
+            && (pattern[p] == '_' || pattern[p] == text[t])
+        {
+            t += 1;
+            p += 1;
+        } else if let Some((after_wildcard, skipped)) = backtrack {
+            backtrack = Some((after_wildcard, skipped + 1));
+            p = after_wildcard;
+            t = skipped + 1;
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '%')
+}
+
+/// Trigrams the way pg_trgm extracts them, from lowercased words padded with spaces
+fn trigrams(text: &str) -> HashSet<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .flat_map(|word| {
+            let padded = format!("  {} ", word).chars().collect::<Vec<_>>();
+            padded
+                .windows(3)
+                .map(|trigram| trigram.iter().collect::<String>())
+                .collect::<Vec<_>>()
+        }).collect()
+}
+
+/// Same as `similarity` of pg_trgm, the share of trigrams both texts have
+fn similarity(a: &str, b: &str) -> f32 {
+    let (a, b) = (trigrams(a), trigrams(b));
+    let union = a.union(&b).count();
+
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f32 / union as f32
+}
+
+/// Build a predicate matching text against pattern in the same way sql backends do
+fn text_matcher(mode: &MatchMode, pat: &str) -> Result<Box<Fn(&str) -> bool>, ServerError> {
+    let pat = pat.to_owned();
+
+    Ok(match *mode {
+        MatchMode::Prefix => Box::new(move |text| ilike(text, &(pat.to_owned() + "%"))),
+        MatchMode::Contains => {
+            let pat = pat.to_lowercase();
+            Box::new(move |text| text.to_lowercase().contains(&pat))
+        }
+        MatchMode::Exact => Box::new(move |text| text == pat),
+        MatchMode::Regex => {
+            let re = RegexBuilder::new(&pat)
+                .case_insensitive(true)
+                .build()
+                .map_err(|err| ServerError::InvalidPattern(err.to_string()))?;
+            Box::new(move |text| re.is_match(text))
+        }
+        MatchMode::Fuzzy => {
+            Box::new(move |text| similarity(text, &pat) >= FUZZY_MATCH_THRESHOLD)
+        }
+    })
+}
+
+fn compare(a: &Paste, b: &Paste, item: &Item) -> Ordering {
+    match *item {
+        Item::Title => a.title.cmp(&b.title),
+        Item::Body => a.body.cmp(&b.body),
+        Item::CreatedAt => a.created_at.cmp(&b.created_at),
+        Item::ModifiedAt => a.modified_at.cmp(&b.modified_at),
+        Item::Relevance => Ordering::Equal,
+    }
+}
+
+fn compare_key(paste: &Paste, key: &SortKey) -> Ordering {
+    match *key {
+        SortKey::Title(ref title) => paste.title.as_str().cmp(title),
+        SortKey::Body(ref body) => paste.body.as_str().cmp(body),
+        SortKey::CreatedAt(created_at) => paste.created_at.cmp(&created_at),
+        SortKey::ModifiedAt(modified_at) => paste.modified_at.cmp(&modified_at),
+    }
+}
+
+/// Turn an ascending comparison into the order rows are walked in
+fn walk(ord: Ordering, order: &Order, cursor: Option<&Cursor>) -> Ordering {
+    let ord = match *order {
+        Order::Ascend => ord,
+        Order::Decrease => ord.reverse(),
+    };
+
+    match cursor {
+        Some(cursor) if cursor.backward => ord.reverse(),
+        _ => ord,
+    }
+}
+
+/// Whether paste is walked past after the cursor position
+fn is_after(
+    paste: &Paste,
+    cursor: &Cursor,
+    position: &CursorPosition,
+    orderby_list: &[Orderby],
+) -> bool {
+    let ord = orderby_list
+        .iter()
+        .zip(&position.keys)
+        .fold(Ordering::Equal, |ord, (orderby, key)| {
+            ord.then_with(|| walk(compare_key(paste, key), &orderby.order, Some(cursor)))
+        });
+
+    ord.then_with(|| walk(paste.slug.cmp(&position.slug), &Order::Ascend, Some(cursor)))
+        == Ordering::Greater
+}
+
+impl PasteStore for MemoryStore {
+    fn create(&self, new_paste: &NewPaste) -> Result<Option<Paste>, ServerError> {
+        let mut data = self.data();
+
+        // slugs stay taken until the paste is purged, expired or not
+        if data.pastes.iter().any(|paste| paste.slug == new_paste.slug) {
+            return Ok(None);
+        }
+
+        data.last_id += 1;
+        let paste = Paste {
+            id: data.last_id,
+            title: new_paste.title.to_owned(),
+            body: new_paste.body.to_owned(),
+            created_at: *new_paste.created_at,
+            modified_at: *new_paste.modified_at,
+            expires_at: new_paste.expires_at.cloned(),
+            burn_after_read: new_paste.burn_after_read,
+            slug: new_paste.slug.to_owned(),
+            language: new_paste.language.map(str::to_owned),
+            version: 1,
+            password_hash: new_paste.password_hash.map(str::to_owned),
+            cipher: new_paste.cipher.map(str::to_owned),
+            nonce: new_paste.nonce.map(str::to_owned),
+            key_id: new_paste.key_id.map(str::to_owned),
+            data_key: new_paste.data_key.map(str::to_owned),
+            visibility: new_paste.visibility,
+            access_token_hash: new_paste.access_token_hash.map(str::to_owned),
+            edit_token_hash: new_paste.edit_token_hash.map(str::to_owned),
+        };
+        data.pastes.push(paste.clone());
+
+        let pos = data.pastes.len() - 1;
+        data.record_revision(pos);
+
+        Ok(Some(paste))
+    }
+
+    fn get(&self, slug: &str) -> Result<Paste, ServerError> {
+        let mut data = self.data();
+        let pos = data
+            .find(slug, SystemTime::now())
+            .ok_or(ServerError::NotFound)?;
+
+        if data.pastes[pos].burn_after_read {
+            let paste = data.pastes[pos].clone();
+            data.remove_where(|found| found.id == paste.id);
+            return Ok(paste);
+        }
+
+        Ok(data.pastes[pos].clone())
+    }
+
+    fn get_head(&self, slug: &str) -> Result<Paste, ServerError> {
+        self.data().find_head(slug).map(Paste::clone)
+    }
+
+    fn peek(&self, slug: &str) -> Result<Paste, ServerError> {
+        let data = self.data();
+        let pos = data
+            .find(slug, SystemTime::now())
+            .ok_or(ServerError::NotFound)?;
+
+        Ok(data.pastes[pos].clone())
+    }
+
+    fn update(
+        &self,
+        slug: &str,
+        changeset: &PasteChangeset,
+        versions: Option<&[i32]>,
+    ) -> Result<Paste, ServerError> {
+        let mut data = self.data();
+        let pos = match (data.find(slug, SystemTime::now()), versions) {
+            (Some(pos), Some(versions)) if versions.contains(&data.pastes[pos].version) => pos,
+            (Some(pos), None) => pos,
+            (_, Some(_)) => return Err(ServerError::VersionMismatch),
+            (None, None) => return Err(ServerError::NotFound),
+        };
+
+        {
+            let paste = &mut data.pastes[pos];
+            paste.title = changeset.title.to_owned();
+            paste.body = changeset.body.to_owned();
+            paste.language = changeset.language.map(str::to_owned);
+            paste.modified_at = *changeset.modified_at;
+            paste.version += 1;
+        }
+        data.record_revision(pos);
+
+        Ok(data.pastes[pos].clone())
+    }
+
+    fn delete(&self, slug: &str, versions: Option<&[i32]>) -> Result<usize, ServerError> {
+        let at_version = |paste: &Paste| versions.map_or(true, |vs| vs.contains(&paste.version));
+        let deleted = self
+            .data()
+            .remove_where(|paste| paste.slug == slug && at_version(paste));
+
+        match versions {
+            Some(_) if deleted == 0 => Err(ServerError::VersionMismatch),
+            _ => Ok(deleted),
+        }
+    }
+
+    fn list(&self, conds: &PasteListConds) -> Result<Vec<Paste>, ServerError> {
+        let data = self.data();
+        let orderby_list = conds.orderby_list.as_ref().map_or(&[][..], Vec::as_slice);
+        let cursor = conds.cursor.as_ref();
+
+        let mut found = data.filter(conds, SystemTime::now())?;
+
+        if let Some(cursor) = cursor {
+            if let Some(ref position) = cursor.position {
+                found.retain(|paste| is_after(paste, cursor, position, orderby_list));
+            }
+        }
+
+        found.sort_by(|a, b| {
+            let ord = orderby_list.iter().fold(Ordering::Equal, |ord, orderby| {
+                ord.then_with(|| walk(compare(a, b, &orderby.item), &orderby.order, cursor))
+            });
+
+            // cursors tell rows with equal sort keys apart by their slugs
+            match cursor {
+                Some(_) => ord.then_with(|| walk(a.slug.cmp(&b.slug), &Order::Ascend, cursor)),
+                None => ord,
+            }
+        });
+
+        let offset = conds.offset.unwrap_or(0).max(0) as usize;
+        let limit = conds.limit.map_or(found.len(), |limit| limit.max(0) as usize);
+
+        Ok(found
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    fn count(&self, conds: &PasteListConds) -> Result<i64, ServerError> {
+        if conds.q.is_some() {
+            return Err(ServerError::Unsupported("full-text search"));
+        }
+
+        Ok(self.data().filter(conds, SystemTime::now())?.len() as i64)
+    }
+
+    fn list_revisions(&self, slug: &str) -> Result<Vec<PasteRevision>, ServerError> {
+        let data = self.data();
+        let head_id = data.find_head(slug)?.id;
+
+        let mut revisions = data
+            .revisions
+            .iter()
+            .filter(|revision| revision.paste_id == head_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        revisions.sort_by_key(|revision| revision.revision);
+
+        Ok(revisions)
+    }
+
+    fn get_revision(&self, slug: &str, revision: i32) -> Result<PasteRevision, ServerError> {
+        let data = self.data();
+        let head_id = data.find_head(slug)?.id;
+
+        data.revisions
+            .iter()
+            .find(|found| found.paste_id == head_id && found.revision == revision)
+            .cloned()
+            .ok_or(ServerError::NotFound)
+    }
+
+    fn purge_expired(&self, now: SystemTime, _batch_size: i64) -> Result<usize, ServerError> {
+        // nothing is locked for long in memory, so everything goes at once
+        Ok(self
+            .data()
+            .remove_where(|paste| paste.expires_at.map_or(false, |expires_at| expires_at <= now)))
+    }
+
+    fn list_unsealed(
+        &self,
+        key_id: &str,
+        limit: i64,
+    ) -> Result<Vec<(Paste, Vec<PasteRevision>)>, ServerError> {
+        let data = self.data();
+
+        Ok(data
+            .pastes
+            .iter()
+            .filter(|paste| paste.key_id.as_ref().map_or(true, |found| found != key_id))
+            .take(limit as usize)
+            .map(|paste| {
+                let mut revisions = data
+                    .revisions
+                    .iter()
+                    .filter(|revision| revision.paste_id == paste.id)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                revisions.sort_by_key(|revision| revision.revision);
+                (paste.clone(), revisions)
+            }).collect())
+    }
+
+    fn reseal(&self, paste: &Paste, revisions: &[PasteRevision]) -> Result<(), ServerError> {
+        let mut data = self.data();
+
+        if let Some(found) = data.pastes.iter_mut().find(|found| found.id == paste.id) {
+            found.title = paste.title.to_owned();
+            found.body = paste.body.to_owned();
+            found.key_id = paste.key_id.to_owned();
+            found.data_key = paste.data_key.to_owned();
+        }
+        for revision in revisions {
+            if let Some(found) = data.revisions.iter_mut().find(|found| found.id == revision.id) {
+                found.title = revision.title.to_owned();
+                found.body = revision.body.to_owned();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_edit_token(&self, slug: &str, edit_token_hash: &str) -> Result<(), ServerError> {
+        let mut data = self.data();
+        let paste = data
+            .pastes
+            .iter_mut()
+            .find(|paste| paste.slug == slug)
+            .ok_or(ServerError::NotFound)?;
+        paste.edit_token_hash = Some(edit_token_hash.to_owned());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::common::{
+    config::{Config, DatabaseKind},
+    error::ServerError,
+};
+use crate::models::{
+    list::PasteListConds,
+    paste::{ListedPaste, NewPaste, Paste, PasteChangeset},
+    revision::PasteRevision,
+};
+
+#[macro_use]
+pub mod macros;
+pub mod memory;
+pub mod pg;
+pub mod sqlite;
+
+/// Persistence of pastes and their revisions, expired pastes are never returned
+pub trait PasteStore: Send + Sync {
+    /// Insert a paste along with its first revision, yields nothing if the slug is already taken
+    fn create(&self, new_paste: &NewPaste) -> Result<Option<Paste>, ServerError>;
+
+    /// Fetch a paste, burn-after-read pastes are deleted by the same call
+    fn get(&self, slug: &str) -> Result<Paste, ServerError>;
+
+    /// Fetch a paste without consuming it, so burn-after-read pastes are never found
+    fn get_head(&self, slug: &str) -> Result<Paste, ServerError>;
+
+    /// Fetch a paste without consuming it, burn-after-read or not, to check it before `get`
+    fn peek(&self, slug: &str) -> Result<Paste, ServerError>;
+
+    /// Overwrite a paste and record its new content as the newest revision, bumping its
+    /// version. Given `versions` the paste has to be at one of them, or be it gone, the
+    /// update fails with `VersionMismatch`
+    fn update(
+        &self,
+        slug: &str,
+        changeset: &PasteChangeset,
+        versions: Option<&[i32]>,
+    ) -> Result<Paste, ServerError>;
+
+    /// Delete a paste, on the same condition on `versions` as `update`
+    fn delete(&self, slug: &str, versions: Option<&[i32]>) -> Result<usize, ServerError>;
+
+    /// Find public pastes matching all given conditions, burn-after-read pastes are never listed
+    fn list(&self, conds: &PasteListConds) -> Result<Vec<Paste>, ServerError>;
+
+    /// Same as `list` but only pastes matching full-text query `q`, ranked by relevance
+    fn search(&self, _q: &str, _conds: &PasteListConds) -> Result<Vec<ListedPaste>, ServerError> {
+        Err(ServerError::Unsupported("full-text search"))
+    }
+
+    /// Count pastes `list`, or `search` if a query is given, would find without any paging
+    fn count(&self, conds: &PasteListConds) -> Result<i64, ServerError>;
+
+    fn list_revisions(&self, slug: &str) -> Result<Vec<PasteRevision>, ServerError>;
+
+    fn get_revision(&self, slug: &str, revision: i32) -> Result<PasteRevision, ServerError>;
+
+    /// Delete pastes expired before `now` in batches, returns how many are gone
+    fn purge_expired(&self, now: SystemTime, batch_size: i64) -> Result<usize, ServerError>;
+
+    /// Up to `limit` pastes in any state, with their revisions, whose data keys are not
+    /// wrapped by master key `key_id`, those stored in plaintext included
+    fn list_unsealed(
+        &self,
+        key_id: &str,
+        limit: i64,
+    ) -> Result<Vec<(Paste, Vec<PasteRevision>)>, ServerError>;
+
+    /// Overwrite title, body and keys of a paste and titles and bodies of its revisions
+    /// with the same sealed anew, leaving versions and times alone
+    fn reseal(&self, paste: &Paste, revisions: &[PasteRevision]) -> Result<(), ServerError>;
+
+    /// Replace the hash of the edit token of a paste in any state, `NotFound` if there is none
+    fn set_edit_token(&self, slug: &str, edit_token_hash: &str) -> Result<(), ServerError>;
+}
+
+/// Open the storage backend selected in config
+pub fn open(config: &Config) -> Arc<PasteStore> {
+    match config.database.kind {
+        DatabaseKind::Postgres => Arc::new(pg::PgStore::new(pg::create_pool(&config.postgres))),
+        DatabaseKind::Sqlite => Arc::new(sqlite::SqliteStore::new(sqlite::create_pool(
+            &config.sqlite,
+        ))),
+        DatabaseKind::Memory => Arc::new(memory::MemoryStore::default()),
+    }
+}
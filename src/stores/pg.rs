@@ -0,0 +1,493 @@
+use std::time::SystemTime;
+
+use diesel::{
+    self,
+    dsl::{count_star, sql},
+    expression::BoxableExpression,
+    pg::{Pg, PgConnection},
+    prelude::*,
+    r2d2::{ConnectionManager, Pool, PooledConnection},
+    result::Error as DieselError,
+    sql_types::{Bool, Float, Text},
+};
+
+use crate::common::{
+    config::PostgresConfig,
+    constant::FUZZY_MATCH_THRESHOLD,
+    error::ServerError,
+    filter::{Cond, Filter, TextOp, TimeOp},
+};
+use crate::models::{
+    list::{CmpOp, Item, MatchMode, Order, PasteListConds, SortKey, TimeCondition},
+    paste::{ListedPaste, NewPaste, Paste, PasteChangeset, Visibility},
+    revision::{NewPasteRevision, PasteRevision},
+    schema::{paste_revisions, pastes},
+};
+use crate::stores::PasteStore;
+
+pub type PgPool = Pool<ConnectionManager<PgConnection>>;
+
+type Predicate = Box<BoxableExpression<pastes::table, Pg, SqlType = Bool>>;
+
+// text search configuration `search_vector` is generated with
+const SEARCH_CONFIG: &str = "'english'";
+const HEADLINE_OPTIONS: &str = "StartSel=<mark>, StopSel=</mark>, MaxFragments=2";
+
+// postgres text search types, never loaded but only passed between functions in a query
+#[derive(Clone, Copy, QueryId)]
+pub struct TsVector;
+#[derive(Clone, Copy, QueryId)]
+pub struct TsQuery;
+
+diesel_infix_operator!(Matches, " @@ ");
+
+// pg_trgm, the `%` operator is what the trigram indexes can serve
+sql_function!(fn similarity(a: Text, b: Text) -> Float);
+diesel_infix_operator!(Similar, " % ");
+diesel_infix_operator!(RegexMatches, " ~* ");
+
+// text search functions are written out as sql, declaring them with `sql_function!` would
+// ask the sqlite backend to know their postgres only types
+macro_rules! tsquery {
+    ($q:expr) => {
+        sql::<TsQuery>(&format!("websearch_to_tsquery({}, ", SEARCH_CONFIG))
+            .bind::<Text, _>($q.to_owned())
+            .sql(")")
+    };
+}
+
+macro_rules! rank {
+    ($q:expr) => {
+        sql::<Float>("ts_rank(search_vector, ")
+            .bind::<TsQuery, _>(tsquery!($q))
+            .sql(")")
+    };
+}
+
+// matches in `$document` with the text around them, tagged as `HEADLINE_OPTIONS` asks for
+macro_rules! headline {
+    ($document:expr, $q:expr) => {
+        sql::<Text>(&format!("ts_headline({}, ", SEARCH_CONFIG))
+            .bind::<Text, _>($document)
+            .sql(", ")
+            .bind::<TsQuery, _>(tsquery!($q))
+            .sql(", ")
+            .bind::<Text, _>(HEADLINE_OPTIONS)
+            .sql(")")
+    };
+}
+
+macro_rules! match_text {
+    ($query:expr, $column:expr, $mode:expr, $pat:expr) => {
+        match $mode {
+            MatchMode::Prefix => $query.filter($column.ilike($pat.to_owned() + "%")),
+            MatchMode::Contains => $query.filter($column.ilike(contains_pattern(&$pat))),
+            MatchMode::Exact => $query.filter($column.eq($pat.as_str())),
+            MatchMode::Regex => {
+                $query.filter(RegexMatches::new($column, $pat.as_str().into_sql::<Text>()))
+            }
+            // `%` only narrows down with the default `pg_trgm.similarity_threshold` of 0.3,
+            // the threshold of our own is applied on the rows it lets through
+            MatchMode::Fuzzy => $query
+                .filter(Similar::new($column, $pat.as_str().into_sql::<Text>()))
+                .filter(similarity($column, $pat.as_str()).ge(FUZZY_MATCH_THRESHOLD)),
+        }
+    };
+}
+
+macro_rules! text_cond {
+    ($column:expr, $op:expr, $value:expr) => {
+        match $op {
+            TextOp::Eq => Box::new($column.eq($value.to_owned())),
+            TextOp::Ne => Box::new($column.ne($value.to_owned())),
+            TextOp::Contains => Box::new($column.ilike(contains_pattern($value))),
+            TextOp::NotContains => Box::new($column.not_ilike(contains_pattern($value))),
+        }
+    };
+}
+
+// filters shared by listing, searching and counting
+macro_rules! filter_list {
+    ($query:expr, $conds:expr, $now:expr) => {{
+        let mut query = $query
+            .filter(unexpired!($now))
+            .filter(burn_after_read.eq(false))
+            .filter(visibility.eq(Visibility::Public));
+
+        if let Some(ref title_pat) = $conds.title_pat {
+            query = match_text!(query, title, $conds.title_mode, title_pat);
+        }
+
+        if let Some(ref body_pat) = $conds.body_pat {
+            query = match_text!(query, body, $conds.body_mode, body_pat);
+        }
+
+        if let Some(ref lang) = $conds.language {
+            query = query.filter(language.eq(lang.as_str()));
+        }
+
+        if let Some(ref cond) = $conds.created_at {
+            query = cmp_time!(query, created_at, cond, SystemTime::from);
+        }
+
+        if let Some(ref cond) = $conds.modified_at {
+            query = cmp_time!(query, modified_at, cond, SystemTime::from);
+        }
+
+        if let Some(ref filter) = $conds.filter {
+            query = query.filter(compile_filter(filter, false));
+        }
+
+        if $conds.touches_body() {
+            query = query.filter(password_hash.is_null().and(cipher.is_null()));
+        }
+
+        query
+    }};
+}
+
+pub fn create_pool(config: &PostgresConfig) -> PgPool {
+    let manager = ConnectionManager::<PgConnection>::new(config.database_url());
+    Pool::builder()
+        .build(manager)
+        .expect("cannot build database connection pool")
+}
+
+pub struct PgStore {
+    pool: PgPool,
+}
+
+impl PgStore {
+    pub fn new(pool: PgPool) -> Self {
+        PgStore { pool }
+    }
+
+    fn conn(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, ServerError> {
+        self.pool.get().map_err(ServerError::R2d2)
+    }
+}
+
+/// Pattern of `ILIKE` matching text containing value, wildcards in value match themselves
+fn contains_pattern(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{}%", escaped)
+}
+
+compile_filter!(SystemTime::from);
+
+/// Bad regular expressions only show up once postgres compiles them
+fn map_pattern_err(err: DieselError) -> ServerError {
+    match err {
+        DieselError::DatabaseError(_, ref info)
+            if info.message().starts_with("invalid regular expression") =>
+        {
+            ServerError::InvalidPattern(info.message().to_owned())
+        }
+        err => ServerError::Database(err),
+    }
+}
+
+record_revision!(PgConnection, Paste, NewPasteRevision);
+
+find_head_id!(PgConnection, SystemTime::from);
+
+impl PasteStore for PgStore {
+    fn create(&self, new_paste: &NewPaste) -> Result<Option<Paste>, ServerError> {
+        use crate::models::schema::pastes::dsl::*;
+
+        let conn = self.conn()?;
+
+        conn.transaction::<_, DieselError, _>(|| {
+            // yields nothing if the slug is already taken
+            let created = diesel::insert_into(pastes)
+                .values(new_paste)
+                .on_conflict_do_nothing()
+                .get_result::<Paste>(&conn)
+                .optional()?;
+
+            if let Some(ref paste) = created {
+                record_revision(&conn, paste)?;
+            }
+
+            Ok(created)
+        }).map_err(ServerError::Database)
+    }
+
+    fn get(&self, paste_slug: &str) -> Result<Paste, ServerError> {
+        get_paste!(self.conn()?, paste_slug, Paste, SystemTime::from)
+    }
+
+    fn get_head(&self, paste_slug: &str) -> Result<Paste, ServerError> {
+        use crate::models::schema::pastes::dsl::*;
+
+        let now = SystemTime::now();
+
+        pastes
+            .filter(slug.eq(paste_slug))
+            .filter(unexpired!(now))
+            .filter(burn_after_read.eq(false))
+            .get_result::<Paste>(&self.conn()?)
+            .map_err(ServerError::Database)
+    }
+
+    fn peek(&self, paste_slug: &str) -> Result<Paste, ServerError> {
+        use crate::models::schema::pastes::dsl::*;
+
+        let now = SystemTime::now();
+
+        pastes
+            .filter(slug.eq(paste_slug))
+            .filter(unexpired!(now))
+            .get_result::<Paste>(&self.conn()?)
+            .map_err(ServerError::Database)
+    }
+
+    fn update(
+        &self,
+        paste_slug: &str,
+        changeset: &PasteChangeset,
+        versions: Option<&[i32]>,
+    ) -> Result<Paste, ServerError> {
+        use crate::models::schema::pastes::dsl::*;
+
+        let conn = self.conn()?;
+        let now = SystemTime::now();
+        let target = pastes.filter(slug.eq(paste_slug)).filter(unexpired!(now));
+
+        conn.transaction::<_, ServerError, _>(|| {
+            // the update locks the head row, so revisions of one paste never interleave
+            let paste = match versions {
+                Some(versions) => diesel::update(target.filter(version.eq_any(versions)))
+                    .set((changeset, version.eq(version + 1)))
+                    .get_result::<Paste>(&conn)
+                    .optional()?
+                    .ok_or(ServerError::VersionMismatch)?,
+                None => diesel::update(target)
+                    .set((changeset, version.eq(version + 1)))
+                    .get_result::<Paste>(&conn)?,
+            };
+
+            record_revision(&conn, &paste)?;
+
+            Ok(paste)
+        })
+    }
+
+    fn delete(&self, paste_slug: &str, versions: Option<&[i32]>) -> Result<usize, ServerError> {
+        use crate::models::schema::pastes::dsl::*;
+
+        let target = pastes.filter(slug.eq(paste_slug));
+
+        match versions {
+            Some(versions) => {
+                let deleted = diesel::delete(target.filter(version.eq_any(versions)))
+                    .execute(&self.conn()?)?;
+                if deleted == 0 {
+                    return Err(ServerError::VersionMismatch);
+                }
+                Ok(deleted)
+            }
+            None => diesel::delete(target)
+                .execute(&self.conn()?)
+                .map_err(ServerError::Database),
+        }
+    }
+
+    fn list(&self, conds: &PasteListConds) -> Result<Vec<Paste>, ServerError> {
+        use crate::models::schema::pastes::dsl::*;
+
+        let now = SystemTime::now();
+        let mut query = paginate!(filter_list!(pastes.into_boxed(), conds, now), conds);
+        let orderby_list = conds.orderby_list.as_ref().map_or(&[][..], Vec::as_slice);
+
+        match conds.cursor {
+            Some(ref cursor) => {
+                if let Some(ref position) = cursor.position {
+                    query = query.filter(after_position!(
+                        cursor,
+                        position,
+                        orderby_list,
+                        Pg,
+                        SystemTime::from
+                    ));
+                }
+                query = cursor_order!(query, cursor, orderby_list);
+            }
+            None => for orderby in orderby_list {
+                query = orderby!(query, orderby.item, orderby.order);
+            },
+        }
+
+        query
+            .load::<Paste>(&self.conn()?)
+            .map_err(map_pattern_err)
+    }
+
+    fn search(&self, q: &str, conds: &PasteListConds) -> Result<Vec<ListedPaste>, ServerError> {
+        use crate::models::schema::pastes::{all_columns, dsl::*};
+
+        let now = SystemTime::now();
+        let headline = headline!(body, q);
+        let mut query = paginate!(
+            filter_list!(
+                pastes
+                    .select((all_columns, rank!(q), headline))
+                    .filter(Matches::new(sql::<TsVector>("search_vector"), tsquery!(q)))
+                    .into_boxed(),
+                conds,
+                now
+            ),
+            conds
+        );
+
+        match conds.orderby_list {
+            Some(ref orderby_list) => for orderby in orderby_list {
+                query = orderby!(query, orderby.item, orderby.order, rank!(q));
+            },
+            None => query = query.order(rank!(q).desc()),
+        }
+
+        query
+            .load::<(Paste, f32, String)>(&self.conn()?)
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|(paste, rank, snippet)| ListedPaste {
+                        paste,
+                        rank: Some(rank),
+                        snippet: Some(snippet),
+                    }).collect()
+            }).map_err(map_pattern_err)
+    }
+
+    fn count(&self, conds: &PasteListConds) -> Result<i64, ServerError> {
+        use crate::models::schema::pastes::dsl::*;
+
+        let now = SystemTime::now();
+        let mut query = filter_list!(pastes.select(count_star()).into_boxed(), conds, now);
+
+        if let Some(ref q) = conds.q {
+            query = query.filter(Matches::new(sql::<TsVector>("search_vector"), tsquery!(q)));
+        }
+
+        query
+            .get_result::<i64>(&self.conn()?)
+            .map_err(map_pattern_err)
+    }
+
+    fn list_revisions(&self, slug: &str) -> Result<Vec<PasteRevision>, ServerError> {
+        let conn = self.conn()?;
+        let head_id = find_head_id(&conn, slug).map_err(ServerError::Database)?;
+
+        paste_revisions::table
+            .filter(paste_revisions::paste_id.eq(head_id))
+            .order(paste_revisions::revision.asc())
+            .load(&conn)
+            .map_err(ServerError::Database)
+    }
+
+    fn get_revision(&self, slug: &str, revision: i32) -> Result<PasteRevision, ServerError> {
+        let conn = self.conn()?;
+        let head_id = find_head_id(&conn, slug).map_err(ServerError::Database)?;
+
+        paste_revisions::table
+            .filter(paste_revisions::paste_id.eq(head_id))
+            .filter(paste_revisions::revision.eq(revision))
+            .get_result(&conn)
+            .map_err(ServerError::Database)
+    }
+
+    fn purge_expired(&self, now: SystemTime, batch_size: i64) -> Result<usize, ServerError> {
+        use crate::models::schema::pastes::dsl::*;
+
+        let conn = self.conn()?;
+        let mut purged = 0;
+
+        // delete in batches so that a big backlog doesn't hold locks for long
+        loop {
+            let expired_ids = pastes
+                .select(id)
+                .filter(expires_at.le(now))
+                .limit(batch_size)
+                .load::<i64>(&conn)
+                .map_err(ServerError::Database)?;
+            let batch_len = expired_ids.len() as i64;
+
+            if batch_len == 0 {
+                break;
+            }
+
+            purged += diesel::delete(pastes.filter(id.eq_any(expired_ids)))
+                .execute(&conn)
+                .map_err(ServerError::Database)?;
+
+            if batch_len < batch_size {
+                break;
+            }
+        }
+
+        Ok(purged)
+    }
+
+    fn list_unsealed(
+        &self,
+        key_id: &str,
+        limit: i64,
+    ) -> Result<Vec<(Paste, Vec<PasteRevision>)>, ServerError> {
+        let conn = self.conn()?;
+
+        pastes::table
+            .filter(pastes::key_id.is_null().or(pastes::key_id.ne(key_id)))
+            .order(pastes::id.asc())
+            .limit(limit)
+            .load::<Paste>(&conn)?
+            .into_iter()
+            .map(|paste| -> Result<_, ServerError> {
+                let revisions = paste_revisions::table
+                    .filter(paste_revisions::paste_id.eq(paste.id))
+                    .order(paste_revisions::revision.asc())
+                    .load::<PasteRevision>(&conn)?;
+                Ok((paste, revisions))
+            }).collect()
+    }
+
+    fn reseal(&self, paste: &Paste, revisions: &[PasteRevision]) -> Result<(), ServerError> {
+        let conn = self.conn()?;
+
+        conn.transaction::<_, ServerError, _>(|| {
+            diesel::update(pastes::table.find(paste.id))
+                .set((
+                    pastes::title.eq(&paste.title),
+                    pastes::body.eq(&paste.body),
+                    pastes::key_id.eq(&paste.key_id),
+                    pastes::data_key.eq(&paste.data_key),
+                )).execute(&conn)?;
+
+            for revision in revisions {
+                diesel::update(paste_revisions::table.find(revision.id))
+                    .set((
+                        paste_revisions::title.eq(&revision.title),
+                        paste_revisions::body.eq(&revision.body),
+                    )).execute(&conn)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    fn set_edit_token(&self, paste_slug: &str, token_hash: &str) -> Result<(), ServerError> {
+        use crate::models::schema::pastes::dsl::*;
+
+        let updated = diesel::update(pastes.filter(slug.eq(paste_slug)))
+            .set(edit_token_hash.eq(token_hash))
+            .execute(&self.conn()?)?;
+        if updated == 0 {
+            return Err(ServerError::NotFound);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,619 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use diesel::{
+    self,
+    connection::SimpleConnection,
+    expression::BoxableExpression,
+    prelude::*,
+    r2d2::{ConnectionManager, CustomizeConnection, Error as R2d2Error, Pool, PooledConnection},
+    result::Error as DieselError,
+    sql_types::{Bool, Integer, Text},
+    sqlite::{Sqlite, SqliteConnection},
+};
+
+use crate::common::{
+    config::SqliteConfig,
+    error::ServerError,
+    filter::{Cond, Filter, TextOp, TimeOp},
+};
+use crate::models::{
+    list::{CmpOp, Item, MatchMode, Order, PasteListConds, SortKey, TimeCondition},
+    paste::{NewPaste, Paste, PasteChangeset, Visibility},
+    revision::PasteRevision,
+};
+use crate::stores::PasteStore;
+
+use self::schema::{paste_revisions, pastes};
+
+// sqlite has no timestamp type, so times are stored as microseconds since unix epoch
+pub mod schema {
+    table! {
+        pastes (id) {
+            id -> BigInt,
+            title -> Text,
+            body -> Text,
+            created_at -> BigInt,
+            modified_at -> BigInt,
+            expires_at -> Nullable<BigInt>,
+            burn_after_read -> Bool,
+            slug -> Text,
+            language -> Nullable<Text>,
+            version -> Integer,
+            password_hash -> Nullable<Text>,
+            cipher -> Nullable<Text>,
+            nonce -> Nullable<Text>,
+            key_id -> Nullable<Text>,
+            data_key -> Nullable<Text>,
+            visibility -> Text,
+            access_token_hash -> Nullable<Text>,
+            edit_token_hash -> Nullable<Text>,
+        }
+    }
+
+    table! {
+        paste_revisions (id) {
+            id -> BigInt,
+            paste_id -> BigInt,
+            revision -> Integer,
+            title -> Text,
+            body -> Text,
+            created_at -> BigInt,
+        }
+    }
+
+    joinable!(paste_revisions -> pastes (paste_id));
+
+    allow_tables_to_appear_in_same_query!(pastes, paste_revisions);
+}
+
+pub type SqlitePool = Pool<ConnectionManager<SqliteConnection>>;
+
+type Predicate = Box<BoxableExpression<pastes::table, Sqlite, SqlType = Bool>>;
+
+// LOWER of sqlite only folds ascii letters, the same as LIKE does
+sql_function!(fn lower(text: Text) -> Text);
+sql_function!(fn instr(haystack: Text, needle: Text) -> Integer);
+
+#[derive(Debug)]
+pub struct SqliteConnCustomizer;
+
+impl CustomizeConnection<SqliteConnection, R2d2Error> for SqliteConnCustomizer {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), R2d2Error> {
+        // revisions rely on ON DELETE CASCADE, and writers queue up instead of failing
+        conn.batch_execute("PRAGMA foreign_keys = ON; PRAGMA busy_timeout = 5000;")
+            .map_err(R2d2Error::QueryError)
+    }
+}
+
+// LIKE of sqlite ignores case of ascii letters, which is as close to ILIKE as it gets
+macro_rules! match_text {
+    ($query:expr, $column:expr, $mode:expr, $pat:expr) => {
+        match $mode {
+            MatchMode::Prefix => $query.filter($column.like($pat.to_owned() + "%")),
+            // wildcards in the pattern match themselves, see `text_cond!`
+            MatchMode::Contains => {
+                $query.filter(instr(lower($column), lower($pat.to_owned())).gt(0))
+            }
+            MatchMode::Exact => $query.filter($column.eq($pat.as_str())),
+            MatchMode::Regex => return Err(ServerError::Unsupported("regex match")),
+            MatchMode::Fuzzy => return Err(ServerError::Unsupported("fuzzy match")),
+        }
+    };
+}
+
+// sqlite has no default escape character for LIKE, so substrings are looked up with INSTR
+macro_rules! text_cond {
+    ($column:expr, $op:expr, $value:expr) => {
+        match $op {
+            TextOp::Eq => Box::new($column.eq($value.to_owned())),
+            TextOp::Ne => Box::new($column.ne($value.to_owned())),
+            TextOp::Contains => Box::new(instr(lower($column), lower($value.to_owned())).gt(0)),
+            TextOp::NotContains => Box::new(instr(lower($column), lower($value.to_owned())).eq(0)),
+        }
+    };
+}
+
+// filters shared by listing and counting
+macro_rules! filter_list {
+    ($query:expr, $conds:expr, $now:expr) => {{
+        let mut query = $query
+            .filter(unexpired!($now))
+            .filter(burn_after_read.eq(false))
+            .filter(visibility.eq(Visibility::Public));
+
+        if let Some(ref title_pat) = $conds.title_pat {
+            query = match_text!(query, title, $conds.title_mode, title_pat);
+        }
+
+        if let Some(ref body_pat) = $conds.body_pat {
+            query = match_text!(query, body, $conds.body_mode, body_pat);
+        }
+
+        if let Some(ref lang) = $conds.language {
+            query = query.filter(language.eq(lang.as_str()));
+        }
+
+        if let Some(ref cond) = $conds.created_at {
+            query = cmp_time!(query, created_at, cond, to_micros);
+        }
+
+        if let Some(ref cond) = $conds.modified_at {
+            query = cmp_time!(query, modified_at, cond, to_micros);
+        }
+
+        if let Some(ref filter) = $conds.filter {
+            query = query.filter(compile_filter(filter, false));
+        }
+
+        if $conds.touches_body() {
+            query = query.filter(password_hash.is_null().and(cipher.is_null()));
+        }
+
+        query
+    }};
+}
+
+compile_filter!(to_micros);
+
+pub fn create_pool(config: &SqliteConfig) -> SqlitePool {
+    let manager = ConnectionManager::<SqliteConnection>::new(config.path.to_owned());
+    Pool::builder()
+        .connection_customizer(Box::new(SqliteConnCustomizer))
+        .build(manager)
+        .expect("cannot build database connection pool")
+}
+
+fn to_micros(time: SystemTime) -> i64 {
+    // times past what fits are clamped, user given ones included, which still compare the
+    // same against any time stored
+    let micros = |duration: Duration| {
+        let secs = duration.as_secs().min(i64::max_value() as u64 / 1_000_000) as i64;
+        (secs * 1_000_000).saturating_add(i64::from(duration.subsec_micros()))
+    };
+
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => micros(duration),
+        Err(err) => -micros(err.duration()),
+    }
+}
+
+fn from_micros(micros: i64) -> SystemTime {
+    if micros >= 0 {
+        UNIX_EPOCH + Duration::from_micros(micros as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_micros(-micros as u64)
+    }
+}
+
+#[derive(Queryable)]
+struct PasteRow {
+    id: i64,
+    title: String,
+    body: String,
+    created_at: i64,
+    modified_at: i64,
+    expires_at: Option<i64>,
+    burn_after_read: bool,
+    slug: String,
+    language: Option<String>,
+    version: i32,
+    password_hash: Option<String>,
+    cipher: Option<String>,
+    nonce: Option<String>,
+    key_id: Option<String>,
+    data_key: Option<String>,
+    visibility: Visibility,
+    access_token_hash: Option<String>,
+    edit_token_hash: Option<String>,
+}
+
+impl From<PasteRow> for Paste {
+    fn from(row: PasteRow) -> Self {
+        Paste {
+            id: row.id,
+            title: row.title,
+            body: row.body,
+            created_at: from_micros(row.created_at),
+            modified_at: from_micros(row.modified_at),
+            expires_at: row.expires_at.map(from_micros),
+            burn_after_read: row.burn_after_read,
+            slug: row.slug,
+            language: row.language,
+            version: row.version,
+            password_hash: row.password_hash,
+            cipher: row.cipher,
+            nonce: row.nonce,
+            key_id: row.key_id,
+            data_key: row.data_key,
+            visibility: row.visibility,
+            access_token_hash: row.access_token_hash,
+            edit_token_hash: row.edit_token_hash,
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "pastes"]
+struct NewPasteRow<'a> {
+    title: &'a str,
+    body: &'a str,
+    created_at: i64,
+    modified_at: i64,
+    expires_at: Option<i64>,
+    burn_after_read: bool,
+    slug: &'a str,
+    language: Option<&'a str>,
+    password_hash: Option<&'a str>,
+    cipher: Option<&'a str>,
+    nonce: Option<&'a str>,
+    key_id: Option<&'a str>,
+    data_key: Option<&'a str>,
+    visibility: Visibility,
+    access_token_hash: Option<&'a str>,
+    edit_token_hash: Option<&'a str>,
+}
+
+#[derive(AsChangeset)]
+#[table_name = "pastes"]
+#[changeset_options(treat_none_as_null = "true")]
+struct PasteChangesetRow<'a> {
+    title: &'a str,
+    body: &'a str,
+    language: Option<&'a str>,
+    modified_at: i64,
+}
+
+#[derive(Queryable)]
+struct PasteRevisionRow {
+    id: i64,
+    paste_id: i64,
+    revision: i32,
+    title: String,
+    body: String,
+    created_at: i64,
+}
+
+impl From<PasteRevisionRow> for PasteRevision {
+    fn from(row: PasteRevisionRow) -> Self {
+        PasteRevision {
+            id: row.id,
+            paste_id: row.paste_id,
+            revision: row.revision,
+            title: row.title,
+            body: row.body,
+            created_at: from_micros(row.created_at),
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "paste_revisions"]
+struct NewPasteRevisionRow<'a> {
+    paste_id: i64,
+    revision: i32,
+    title: &'a str,
+    body: &'a str,
+    created_at: &'a i64,
+}
+
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        SqliteStore { pool }
+    }
+
+    fn conn(&self) -> Result<PooledConnection<ConnectionManager<SqliteConnection>>, ServerError> {
+        self.pool.get().map_err(ServerError::R2d2)
+    }
+}
+
+record_revision!(SqliteConnection, PasteRow, NewPasteRevisionRow);
+
+find_head_id!(SqliteConnection, to_micros);
+
+// sqlite has no RETURNING, so written rows are read back within the same transaction
+impl PasteStore for SqliteStore {
+    fn create(&self, new_paste: &NewPaste) -> Result<Option<Paste>, ServerError> {
+        use self::schema::pastes::dsl::*;
+
+        let conn = self.conn()?;
+        let row = NewPasteRow {
+            title: new_paste.title,
+            body: new_paste.body,
+            created_at: to_micros(*new_paste.created_at),
+            modified_at: to_micros(*new_paste.modified_at),
+            expires_at: new_paste.expires_at.map(|time| to_micros(*time)),
+            burn_after_read: new_paste.burn_after_read,
+            slug: new_paste.slug,
+            language: new_paste.language,
+            password_hash: new_paste.password_hash,
+            cipher: new_paste.cipher,
+            nonce: new_paste.nonce,
+            key_id: new_paste.key_id,
+            data_key: new_paste.data_key,
+            visibility: new_paste.visibility,
+            access_token_hash: new_paste.access_token_hash,
+            edit_token_hash: new_paste.edit_token_hash,
+        };
+
+        conn.transaction::<_, DieselError, _>(|| {
+            // inserts nothing if the slug is already taken
+            let inserted = diesel::insert_or_ignore_into(pastes)
+                .values(&row)
+                .execute(&conn)?;
+            if inserted == 0 {
+                return Ok(None);
+            }
+
+            let paste = pastes
+                .filter(slug.eq(new_paste.slug))
+                .get_result::<PasteRow>(&conn)?;
+            record_revision(&conn, &paste)?;
+
+            Ok(Some(Paste::from(paste)))
+        }).map_err(ServerError::Database)
+    }
+
+    fn get(&self, paste_slug: &str) -> Result<Paste, ServerError> {
+        get_paste!(self.conn()?, paste_slug, PasteRow, to_micros)
+    }
+
+    fn get_head(&self, paste_slug: &str) -> Result<Paste, ServerError> {
+        use self::schema::pastes::dsl::*;
+
+        let now = to_micros(SystemTime::now());
+
+        pastes
+            .filter(slug.eq(paste_slug))
+            .filter(unexpired!(now))
+            .filter(burn_after_read.eq(false))
+            .get_result::<PasteRow>(&self.conn()?)
+            .map(Paste::from)
+            .map_err(ServerError::Database)
+    }
+
+    fn peek(&self, paste_slug: &str) -> Result<Paste, ServerError> {
+        use self::schema::pastes::dsl::*;
+
+        let now = to_micros(SystemTime::now());
+
+        pastes
+            .filter(slug.eq(paste_slug))
+            .filter(unexpired!(now))
+            .get_result::<PasteRow>(&self.conn()?)
+            .map(Paste::from)
+            .map_err(ServerError::Database)
+    }
+
+    fn update(
+        &self,
+        paste_slug: &str,
+        changeset: &PasteChangeset,
+        versions: Option<&[i32]>,
+    ) -> Result<Paste, ServerError> {
+        use self::schema::pastes::dsl::*;
+
+        let conn = self.conn()?;
+        let now = to_micros(SystemTime::now());
+        let row = PasteChangesetRow {
+            title: changeset.title,
+            body: changeset.body,
+            language: changeset.language,
+            modified_at: to_micros(*changeset.modified_at),
+        };
+
+        let target = pastes.filter(slug.eq(paste_slug)).filter(unexpired!(now));
+
+        conn.transaction::<_, ServerError, _>(|| {
+            let updated = match versions {
+                Some(versions) => diesel::update(target.filter(version.eq_any(versions)))
+                    .set((&row, version.eq(version + 1)))
+                    .execute(&conn)?,
+                None => diesel::update(target)
+                    .set((&row, version.eq(version + 1)))
+                    .execute(&conn)?,
+            };
+            if updated == 0 {
+                return Err(match versions {
+                    Some(_) => ServerError::VersionMismatch,
+                    None => ServerError::Database(DieselError::NotFound),
+                });
+            }
+
+            let paste = pastes
+                .filter(slug.eq(paste_slug))
+                .get_result::<PasteRow>(&conn)?;
+            record_revision(&conn, &paste)?;
+
+            Ok(Paste::from(paste))
+        })
+    }
+
+    fn delete(&self, paste_slug: &str, versions: Option<&[i32]>) -> Result<usize, ServerError> {
+        use self::schema::pastes::dsl::*;
+
+        let target = pastes.filter(slug.eq(paste_slug));
+
+        match versions {
+            Some(versions) => {
+                let deleted = diesel::delete(target.filter(version.eq_any(versions)))
+                    .execute(&self.conn()?)?;
+                if deleted == 0 {
+                    return Err(ServerError::VersionMismatch);
+                }
+                Ok(deleted)
+            }
+            None => diesel::delete(target)
+                .execute(&self.conn()?)
+                .map_err(ServerError::Database),
+        }
+    }
+
+    fn list(&self, conds: &PasteListConds) -> Result<Vec<Paste>, ServerError> {
+        use self::schema::pastes::dsl::*;
+
+        let now = to_micros(SystemTime::now());
+        let mut query = paginate!(filter_list!(pastes.into_boxed(), conds, now), conds);
+        let orderby_list = conds.orderby_list.as_ref().map_or(&[][..], Vec::as_slice);
+
+        match conds.cursor {
+            Some(ref cursor) => {
+                if let Some(ref position) = cursor.position {
+                    query = query.filter(after_position!(
+                        cursor,
+                        position,
+                        orderby_list,
+                        Sqlite,
+                        to_micros
+                    ));
+                }
+                query = cursor_order!(query, cursor, orderby_list);
+            }
+            None => for orderby in orderby_list {
+                query = orderby!(query, orderby.item, orderby.order);
+            },
+        }
+
+        query
+            .load::<PasteRow>(&self.conn()?)
+            .map(|rows| rows.into_iter().map(Paste::from).collect())
+            .map_err(ServerError::Database)
+    }
+
+    fn count(&self, conds: &PasteListConds) -> Result<i64, ServerError> {
+        use self::schema::pastes::dsl::*;
+        use diesel::dsl::count_star;
+
+        if conds.q.is_some() {
+            return Err(ServerError::Unsupported("full-text search"));
+        }
+
+        let now = to_micros(SystemTime::now());
+
+        filter_list!(pastes.select(count_star()).into_boxed(), conds, now)
+            .get_result::<i64>(&self.conn()?)
+            .map_err(ServerError::Database)
+    }
+
+    fn list_revisions(&self, slug: &str) -> Result<Vec<PasteRevision>, ServerError> {
+        let conn = self.conn()?;
+        let head_id = find_head_id(&conn, slug).map_err(ServerError::Database)?;
+
+        paste_revisions::table
+            .filter(paste_revisions::paste_id.eq(head_id))
+            .order(paste_revisions::revision.asc())
+            .load::<PasteRevisionRow>(&conn)
+            .map(|rows| rows.into_iter().map(PasteRevision::from).collect())
+            .map_err(ServerError::Database)
+    }
+
+    fn get_revision(&self, slug: &str, revision: i32) -> Result<PasteRevision, ServerError> {
+        let conn = self.conn()?;
+        let head_id = find_head_id(&conn, slug).map_err(ServerError::Database)?;
+
+        paste_revisions::table
+            .filter(paste_revisions::paste_id.eq(head_id))
+            .filter(paste_revisions::revision.eq(revision))
+            .get_result::<PasteRevisionRow>(&conn)
+            .map(PasteRevision::from)
+            .map_err(ServerError::Database)
+    }
+
+    fn purge_expired(&self, now: SystemTime, batch_size: i64) -> Result<usize, ServerError> {
+        use self::schema::pastes::dsl::*;
+
+        let conn = self.conn()?;
+        let now = to_micros(now);
+        let mut purged = 0;
+
+        // delete in batches so that a big backlog doesn't block writers for long
+        loop {
+            let expired_ids = pastes
+                .select(id)
+                .filter(expires_at.le(now))
+                .limit(batch_size)
+                .load::<i64>(&conn)
+                .map_err(ServerError::Database)?;
+            let batch_len = expired_ids.len() as i64;
+
+            if batch_len == 0 {
+                break;
+            }
+
+            purged += diesel::delete(pastes.filter(id.eq_any(expired_ids)))
+                .execute(&conn)
+                .map_err(ServerError::Database)?;
+
+            if batch_len < batch_size {
+                break;
+            }
+        }
+
+        Ok(purged)
+    }
+
+    fn list_unsealed(
+        &self,
+        key_id: &str,
+        limit: i64,
+    ) -> Result<Vec<(Paste, Vec<PasteRevision>)>, ServerError> {
+        let conn = self.conn()?;
+
+        pastes::table
+            .filter(pastes::key_id.is_null().or(pastes::key_id.ne(key_id)))
+            .order(pastes::id.asc())
+            .limit(limit)
+            .load::<PasteRow>(&conn)?
+            .into_iter()
+            .map(|row| -> Result<_, ServerError> {
+                let revisions = paste_revisions::table
+                    .filter(paste_revisions::paste_id.eq(row.id))
+                    .order(paste_revisions::revision.asc())
+                    .load::<PasteRevisionRow>(&conn)?
+                    .into_iter()
+                    .map(PasteRevision::from)
+                    .collect();
+                Ok((Paste::from(row), revisions))
+            }).collect()
+    }
+
+    fn reseal(&self, paste: &Paste, revisions: &[PasteRevision]) -> Result<(), ServerError> {
+        let conn = self.conn()?;
+
+        conn.transaction::<_, ServerError, _>(|| {
+            diesel::update(pastes::table.find(paste.id))
+                .set((
+                    pastes::title.eq(&paste.title),
+                    pastes::body.eq(&paste.body),
+                    pastes::key_id.eq(&paste.key_id),
+                    pastes::data_key.eq(&paste.data_key),
+                )).execute(&conn)?;
+
+            for revision in revisions {
+                diesel::update(paste_revisions::table.find(revision.id))
+                    .set((
+                        paste_revisions::title.eq(&revision.title),
+                        paste_revisions::body.eq(&revision.body),
+                    )).execute(&conn)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    fn set_edit_token(&self, paste_slug: &str, token_hash: &str) -> Result<(), ServerError> {
+        use self::schema::pastes::dsl::*;
+
+        let updated = diesel::update(pastes.filter(slug.eq(paste_slug)))
+            .set(edit_token_hash.eq(token_hash))
+            .execute(&self.conn()?)?;
+        if updated == 0 {
+            return Err(ServerError::NotFound);
+        }
+
+        Ok(())
+    }
+}
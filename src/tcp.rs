@@ -0,0 +1,170 @@
+use std::cell::Cell;
+use std::mem;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
+
+use actix::prelude::*;
+use failure::Error;
+use futures::{Async, Future, Poll, Stream};
+use tokio_io::{io::write_all, AsyncRead};
+use tokio_tcp::{TcpListener, TcpStream};
+use tokio_timer::Delay;
+
+use crate::common::{config::TcpConfig, constant, error::UserError};
+use crate::models::{executor::DatabaseExecutor as DbExecutor, paste::Visibility};
+use crate::services::paste::CreatePasteMsg;
+
+/// Start listening for raw tcp connections, everything sent on a connection is
+/// stored as a paste whose url and edit token are written back, e.g. `echo foo | nc host 9999`.
+/// Past `max_connections` open at once, new ones only get an error back
+pub fn listen(
+    config: &TcpConfig,
+    db_chan: Addr<DbExecutor>,
+    slug_length: usize,
+    paste_url_base: String,
+) -> Result<SocketAddr, Error> {
+    let addr = format!("{}:{}", config.ip, config.port).parse::<SocketAddr>()?;
+    let listener = TcpListener::bind(&addr)?;
+    let local_addr = listener.local_addr()?;
+    let max_size = config.max_size;
+    let timeout = Duration::from_secs(config.timeout);
+    let max_connections = config.max_connections;
+    // connections are accepted and handled on the same arbiter
+    let open_connections = Rc::new(Cell::new(0));
+
+    Arbiter::spawn(
+        listener
+            .incoming()
+            // a failed accept must not stop the listener
+            .then(|stream| Ok::<_, ()>(stream.ok()))
+            .filter_map(|stream| stream)
+            .for_each(move |stream| {
+                if open_connections.get() >= max_connections {
+                    let reply = format!("error: {}\n", constant::ERR_MSG_TOO_MANY_CONNECTIONS);
+                    Arbiter::spawn(write_all(stream, reply).map(|_| ()).map_err(|_| ()));
+                    return Ok(());
+                }
+
+                open_connections.set(open_connections.get() + 1);
+                let open_connections = Rc::clone(&open_connections);
+                Arbiter::spawn(
+                    handle_conn(
+                        stream,
+                        db_chan.clone(),
+                        slug_length,
+                        paste_url_base.to_owned(),
+                        max_size,
+                        timeout,
+                    ).then(move |res| {
+                        open_connections.set(open_connections.get() - 1);
+                        res
+                    }),
+                );
+                Ok(())
+            }),
+    );
+
+    Ok(local_addr)
+}
+
+fn handle_conn(
+    stream: TcpStream,
+    db_chan: Addr<DbExecutor>,
+    slug_length: usize,
+    paste_url_base: String,
+    max_size: usize,
+    timeout: Duration,
+) -> impl Future<Item = (), Error = ()> {
+    let (reader, writer) = stream.split();
+
+    ReadPaste::new(reader, max_size, timeout)
+        .and_then(|body| {
+            if body.is_empty() {
+                return Err(UserError::PayloadError(
+                    constant::ERR_MSG_PAYLOAD_EMPTY.to_owned(),
+                ));
+            }
+
+            String::from_utf8(body).map_err(|_| {
+                UserError::PayloadError(constant::ERR_MSG_PAYLOAD_NOT_UTF8.to_owned())
+            })
+        }).and_then(move |body| {
+            db_chan
+                .send(CreatePasteMsg {
+                    title: constant::DEFAULT_PASTE_TITLE.to_owned(),
+                    body,
+                    created_at: SystemTime::now(),
+                    expires_at: None,
+                    burn_after_read: false,
+                    slug: None,
+                    slug_length,
+                    language: None,
+                    password: None,
+                    encryption: None,
+                    visibility: Visibility::Public,
+                }).map_err(|_| UserError::InternalError)
+                .and_then(|res| res.map_err(UserError::from))
+        }).then(move |res| {
+            let reply = match res {
+                Ok(created) => format!(
+                    "{}{}\nedit_token: {}\n",
+                    paste_url_base, created.paste.slug, created.edit_token
+                ),
+                Err(err) => format!("error: {}\n", err),
+            };
+
+            write_all(writer, reply)
+        }).map(|_| ())
+        .map_err(|_| ())
+}
+
+// reads until EOF, clients that never close their end get what they sent before timeout
+struct ReadPaste<R> {
+    reader: R,
+    buf: Vec<u8>,
+    max_size: usize,
+    deadline: Delay,
+}
+
+impl<R: AsyncRead> ReadPaste<R> {
+    fn new(reader: R, max_size: usize, timeout: Duration) -> Self {
+        ReadPaste {
+            reader,
+            buf: vec![],
+            max_size,
+            deadline: Delay::new(Instant::now() + timeout),
+        }
+    }
+}
+
+impl<R: AsyncRead> Future for ReadPaste<R> {
+    type Item = Vec<u8>;
+    type Error = UserError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let mut chunk = [0u8; 4096];
+
+        loop {
+            match self.reader.poll_read(&mut chunk) {
+                Ok(Async::Ready(0)) => break,
+                Ok(Async::Ready(len)) => {
+                    if self.buf.len() + len > self.max_size {
+                        return Err(UserError::PayloadError(
+                            constant::ERR_MSG_PAYLOAD_TOO_LARGE.to_owned(),
+                        ));
+                    }
+                    self.buf.extend_from_slice(&chunk[..len]);
+                }
+                Ok(Async::NotReady) => match self.deadline.poll() {
+                    Ok(Async::Ready(())) => break,
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Err(_) => return Err(UserError::InternalError),
+                },
+                Err(err) => return Err(UserError::PayloadError(err.to_string())),
+            }
+        }
+
+        Ok(Async::Ready(mem::replace(&mut self.buf, vec![])))
+    }
+}
@@ -0,0 +1,193 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::common::{config::EncryptionConfig, crypto::Keyring, error::ServerError};
+use crate::models::{
+    list::{Item, Order, Orderby, PasteListConds},
+    paste::{NewPaste, PasteChangeset, Visibility},
+};
+use crate::services::{
+    paste::{data_key, unseal, unseal_revision, CreatePasteMsg, GetPasteListMsg},
+    rotation::rotate_keys,
+};
+use crate::stores::{memory::MemoryStore, PasteStore};
+use crate::tests::TestSuit;
+
+const OLD_KEY: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=";
+const NEW_KEY: &str = "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=";
+
+fn create_keyring(active_key: &str, keys: &[(&str, &str)]) -> Keyring {
+    Keyring::load(&EncryptionConfig {
+        active_key: active_key.to_owned(),
+        keys: keys
+            .iter()
+            .map(|(id, key)| (id.to_string(), key.to_string()))
+            .collect(),
+        key_file: None,
+        batch_size: 1,
+    }).unwrap()
+}
+
+#[test]
+fn test_seal_and_open() {
+    let keyring = create_keyring("old", &[("old", OLD_KEY)]);
+    let (data_key, wrapped) = keyring.generate().unwrap();
+
+    let sealed = data_key.seal("body", "step 1").unwrap();
+    assert_ne!(sealed, data_key.seal("body", "step 1").unwrap());
+    assert_eq!(data_key.open("body", &sealed).unwrap(), "step 1");
+    assert!(data_key.open("title", &sealed).is_err());
+
+    let unwrapped = keyring.unwrap("old", &wrapped).unwrap();
+    assert_eq!(unwrapped.open("body", &sealed).unwrap(), "step 1");
+    assert!(keyring.unwrap("new", &wrapped).is_err());
+
+    let other_keyring = create_keyring("old", &[("old", NEW_KEY)]);
+    assert!(other_keyring.unwrap("old", &wrapped).is_err());
+}
+
+#[test]
+fn test_load_bad_keyring() {
+    let load = |active_key: &str, key: &str| {
+        Keyring::load(&EncryptionConfig {
+            active_key: active_key.to_owned(),
+            keys: vec![("old".to_owned(), key.to_owned())].into_iter().collect(),
+            key_file: None,
+            batch_size: 1,
+        })
+    };
+
+    assert!(load("old", OLD_KEY).is_ok());
+    assert!(load("new", OLD_KEY).is_err());
+    assert!(load("old", "AAAAAAAAAAAAAAAAAAAAAA==").is_err());
+    assert!(load("old", "not base64").is_err());
+}
+
+#[test]
+fn test_rotate_keys() {
+    let store = MemoryStore::default();
+    let now = SystemTime::now();
+
+    // stored in plaintext before encryption at rest was turned on
+    for &slug in &["deploy", "notes"] {
+        store
+            .create(&NewPaste {
+                title: "deploy runbook",
+                body: "step 1",
+                created_at: &now,
+                modified_at: &now,
+                expires_at: None,
+                burn_after_read: false,
+                slug,
+                language: None,
+                password_hash: None,
+                cipher: None,
+                nonce: None,
+                key_id: None,
+                data_key: None,
+                visibility: Visibility::Public,
+                access_token_hash: None,
+                edit_token_hash: None,
+            }).unwrap()
+            .unwrap();
+    }
+    store
+        .update(
+            "deploy",
+            &PasteChangeset {
+                title: "deploy runbook",
+                body: "step 2",
+                language: None,
+                modified_at: &now,
+            },
+            None,
+        ).unwrap();
+
+    let old_keyring = create_keyring("old", &[("old", OLD_KEY)]);
+    assert_eq!(rotate_keys(&store, &old_keyring, 1).unwrap(), 2);
+    assert_eq!(rotate_keys(&store, &old_keyring, 1).unwrap(), 0);
+
+    let stored = store.get_head("deploy").unwrap();
+    assert_eq!(stored.key_id.as_ref().unwrap(), "old");
+    assert_ne!(stored.body, "step 2");
+
+    // older master keys stay around to open what they sealed until rotated away
+    let new_keyring = create_keyring("new", &[("old", OLD_KEY), ("new", NEW_KEY)]);
+    assert_eq!(rotate_keys(&store, &new_keyring, 500).unwrap(), 2);
+
+    let stored = store.get_head("deploy").unwrap();
+    assert_eq!(stored.key_id.as_ref().unwrap(), "new");
+    assert!(unseal(Some(&old_keyring), stored.clone()).is_err());
+
+    let data_key = data_key(Some(&new_keyring), &stored).unwrap();
+    let paste = unseal(Some(&new_keyring), stored).unwrap();
+    assert_eq!(paste.title, "deploy runbook");
+    assert_eq!(paste.body, "step 2");
+
+    let bodies = store
+        .list_revisions("deploy")
+        .unwrap()
+        .into_iter()
+        .map(|revision| unseal_revision(data_key.as_ref(), revision).unwrap().body)
+        .collect::<Vec<_>>();
+    assert_eq!(bodies, vec!["step 1", "step 2"]);
+}
+
+#[test]
+fn test_list_sealed_pastes() {
+    use futures::future::Future;
+
+    let keyring = create_keyring("old", &[("old", OLD_KEY)]);
+    let executor = TestSuit::create_executor(
+        Arc::new(MemoryStore::default()),
+        Some(Arc::new(keyring)),
+    );
+    for &(title, language) in &[("main.rs", "rust"), ("notes", "text")] {
+        sync_send!(
+            executor,
+            CreatePasteMsg {
+                title: title.to_owned(),
+                body: "step 1".to_owned(),
+                created_at: SystemTime::now(),
+                expires_at: None,
+                burn_after_read: false,
+                slug: None,
+                slug_length: 8,
+                language: Some(language.to_owned()),
+                password: None,
+                encryption: None,
+                visibility: Visibility::Public,
+            }
+        );
+    }
+
+    // columns kept in the clear still narrow down and order sealed pastes
+    let page = sync_send!(
+        executor,
+        GetPasteListMsg(PasteListConds {
+            language: Some("rust".to_owned()),
+            orderby_list: Some(vec![Orderby {
+                item: Item::CreatedAt,
+                order: Order::Decrease,
+            }]),
+            ..Default::default()
+        })
+    );
+    let titles = page
+        .items
+        .into_iter()
+        .map(|item| item.paste.title)
+        .collect::<Vec<_>>();
+    assert_eq!(titles, vec!["main.rs"]);
+
+    let by_title = executor
+        .send(GetPasteListMsg(PasteListConds {
+            title_pat: Some("main".to_owned()),
+            ..Default::default()
+        })).wait()
+        .unwrap();
+    match by_title {
+        Err(ServerError::Unsupported(_)) => (),
+        _ => panic!("sealed titles must not be matched"),
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::common::filter::{parse, Cond, Filter, ParseError, TextOp, TimeOp};
+
+fn cond(cond: Cond) -> Box<Filter> {
+    Box::new(Filter::Cond(cond))
+}
+
+fn parse_err(input: &str) -> (usize, String) {
+    let ParseError { column, reason } = parse(input).unwrap_err();
+    (column, reason)
+}
+
+#[test]
+fn test_parse_filter() {
+    let filter = parse(r#"title ~ "deploy" and (created_at > 2026-01-01 or language = rust)"#);
+    assert_eq!(
+        filter.unwrap(),
+        Filter::And(
+            cond(Cond::Title(TextOp::Contains, "deploy".to_owned())),
+            Box::new(Filter::Or(
+                cond(Cond::CreatedAt(
+                    TimeOp::Gt,
+                    UNIX_EPOCH + Duration::from_secs(1_767_225_600)
+                )),
+                cond(Cond::LanguageIs("rust".to_owned())),
+            )),
+        )
+    );
+
+    // `and` binds tighter than `or`, keywords are case-insensitive
+    let filter = parse(r#"NOT body !~ "a \"quoted\" b" OR title = x AND language != c"#);
+    assert_eq!(
+        filter.unwrap(),
+        Filter::Or(
+            Box::new(Filter::Not(cond(Cond::Body(
+                TextOp::NotContains,
+                "a \"quoted\" b".to_owned()
+            )))),
+            Box::new(Filter::And(
+                cond(Cond::Title(TextOp::Eq, "x".to_owned())),
+                cond(Cond::LanguageIsNot("c".to_owned())),
+            )),
+        )
+    );
+
+    let filter = parse("modified_at <= 2026-01-01T08:00:00+08:00");
+    assert_eq!(
+        filter.unwrap(),
+        Filter::Cond(Cond::ModifiedAt(
+            TimeOp::Le,
+            UNIX_EPOCH + Duration::from_secs(1_767_225_600)
+        ))
+    );
+}
+
+#[test]
+fn test_parse_bad_filter() {
+    assert_eq!(parse_err("").0, 1);
+    assert_eq!(parse_err("author = me"), (1, "unknown field `author`".to_owned()));
+    assert_eq!(
+        parse_err("title > x"),
+        (7, "`>` cannot be applied to title".to_owned())
+    );
+    assert_eq!(parse_err("title ~ \"abc").0, 9);
+    assert_eq!(
+        parse_err("(title = a"),
+        (11, "expected `)`, found the end".to_owned())
+    );
+    assert_eq!(parse_err("title = a body = b").0, 11);
+    assert_eq!(parse_err("created_at > yesterday").0, 14);
+    assert_eq!(
+        parse_err("title = a and $"),
+        (15, "unexpected character `$`".to_owned())
+    );
+
+    let nested = "(".repeat(100) + "title = a" + &")".repeat(100);
+    assert_eq!(parse_err(&nested).1, "filter is nested too deeply");
+    let chained = vec!["title = a"; 1000].join(" or ");
+    assert_eq!(parse_err(&chained).1, "filter has too many conditions");
+}
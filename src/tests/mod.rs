@@ -3,17 +3,33 @@ use std::sync::{Arc, Mutex, MutexGuard};
 use actix::prelude::*;
 // prelude is required for PgConnection::establish()
 use diesel::{
+    connection::SimpleConnection,
     pg::PgConnection,
     prelude::*,
     r2d2::{ConnectionManager, CustomizeConnection, Error as R2d2Error, Pool},
+    sqlite::SqliteConnection,
 };
 
-use crate::common::config::Config;
+use crate::common::{
+    config::{Config, DatabaseKind},
+    crypto::Keyring,
+};
 use crate::models::{executor::DatabaseExecutor as DBExecutor, paste::Paste};
+use crate::stores::{
+    memory::MemoryStore,
+    pg::PgStore,
+    sqlite::{self, SqliteStore},
+    PasteStore,
+};
 
 #[macro_use]
 pub mod macros;
+pub mod crypto;
+pub mod filter;
 pub mod paste;
+pub mod store;
+pub mod tcp;
+pub mod time;
 
 lazy_static! {
     static ref TEST_SUIT: TestSuit = TestSuit::new();
@@ -23,6 +39,16 @@ pub mod constant {
     pub const TEST_CONFIG_FILENAME: &str = "test_config.toml";
     pub const ERR_MSG_BAD_ID: &str = "bad id";
     pub const ERR_MSG_DATA_NOT_FOUND: &str = "data not found";
+    pub const ERR_MSG_SLUG_CONFLICT: &str = "slug already taken";
+    pub const ERR_MSG_PRECONDITION_FAILED: &str = "paste has been changed since it was fetched";
+    pub const ERR_MSG_PASSWORD_REQUIRED: &str =
+        "paste is protected by a password, missing or wrong";
+    pub const ERR_MSG_ENCRYPTED: &str =
+        "paste is encrypted in the client, the server cannot read its body";
+    pub const ERR_MSG_EDIT_TOKEN_REQUIRED: &str =
+        "changing a paste takes the edit token given on its creation";
+    // every paste of the test data is changed with it
+    pub const TEST_EDIT_TOKEN: &str = "test-edit-token";
 }
 
 #[derive(Debug)]
@@ -34,24 +60,32 @@ impl CustomizeConnection<PgConnection, R2d2Error> for TestTxConnCustomizer {
     }
 }
 
-pub struct ResetPool();
+impl CustomizeConnection<SqliteConnection, R2d2Error> for TestTxConnCustomizer {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), R2d2Error> {
+        conn.batch_execute("PRAGMA foreign_keys = ON;")
+            .map_err(R2d2Error::QueryError)?;
+        conn.begin_test_transaction().map_err(R2d2Error::QueryError)
+    }
+}
+
+pub struct ResetStore();
 
-impl Message for ResetPool {
+impl Message for ResetStore {
     type Result = Result<(), ()>;
 }
 
-impl Handler<ResetPool> for DBExecutor {
+impl Handler<ResetStore> for DBExecutor {
     type Result = Result<(), ()>;
 
-    fn handle(&mut self, _msg: ResetPool, _: &mut Self::Context) -> Self::Result {
-        // replace old pool with newly created one
-        self.0 = TestSuit::create_pool(&TEST_SUIT.database_url);
+    fn handle(&mut self, _msg: ResetStore, _: &mut Self::Context) -> Self::Result {
+        // replace old store with newly created one
+        self.0 = TestSuit::create_store(&TEST_SUIT.config, &TEST_SUIT.data);
         Ok(())
     }
 }
 
 struct TestSuit {
-    database_url: String,
+    config: Config,
     data: Vec<Paste>,
     executor: Addr<DBExecutor>,
     locker: Arc<Mutex<()>>,
@@ -60,26 +94,23 @@ struct TestSuit {
 impl TestSuit {
     pub fn new() -> Self {
         let config = Config::load(constant::TEST_CONFIG_FILENAME);
-        let database_url = format!(
-            "postgres://{}:{}@{}/{}",
-            config.postgres.username,
-            config.postgres.password,
-            config.postgres.host,
-            config.postgres.database,
-        );
-
-        let data = Self::create_data(&database_url);
-        let pool = Self::create_pool(&database_url);
-        let executor = Self::create_executor(pool);
+
+        let data = Self::create_data(&config);
+        let store = Self::create_store(&config, &data);
+        let executor = Self::create_executor(store, None);
 
         TestSuit {
-            database_url: database_url.to_owned(),
+            config,
             data,
             executor,
             locker: Arc::new(Mutex::new(())),
         }
     }
 
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
     pub fn data(&self) -> &Vec<Paste> {
         &self.data
     }
@@ -96,11 +127,24 @@ impl TestSuit {
             Err(poisoned) => poisoned.into_inner(),
         };
 
-        sync_send!(self.executor, ResetPool {});
+        sync_send!(self.executor, ResetStore {});
 
         guard
     }
 
+    pub fn create_store(config: &Config, data: &[Paste]) -> Arc<PasteStore> {
+        match config.database.kind {
+            // test data is committed once, each test runs in a transaction never committed
+            DatabaseKind::Postgres => Arc::new(PgStore::new(Self::create_pool(
+                &config.postgres.database_url(),
+            ))),
+            DatabaseKind::Sqlite => Arc::new(SqliteStore::new(Self::create_sqlite_pool(
+                &config.sqlite.path,
+            ))),
+            DatabaseKind::Memory => Arc::new(MemoryStore::with_pastes(data.to_vec())),
+        }
+    }
+
     pub fn create_pool(database_url: &str) -> Pool<ConnectionManager<PgConnection>> {
         let manager = ConnectionManager::<PgConnection>::new(database_url.to_owned());
         Pool::builder()
@@ -110,7 +154,19 @@ impl TestSuit {
             .expect("cannot build database connection pool")
     }
 
-    pub fn create_executor(pool: Pool<ConnectionManager<PgConnection>>) -> Addr<DBExecutor> {
+    pub fn create_sqlite_pool(path: &str) -> Pool<ConnectionManager<SqliteConnection>> {
+        let manager = ConnectionManager::<SqliteConnection>::new(path.to_owned());
+        Pool::builder()
+            .max_size(1)
+            .connection_customizer(Box::new(TestTxConnCustomizer))
+            .build(manager)
+            .expect("cannot build database connection pool")
+    }
+
+    pub fn create_executor(
+        store: Arc<PasteStore>,
+        keyring: Option<Arc<Keyring>>,
+    ) -> Addr<DBExecutor> {
         use std::sync::mpsc::channel;
         use std::thread;
 
@@ -119,7 +175,7 @@ impl TestSuit {
         thread::spawn(move || {
             let sys = actix::System::new("pastebin-test");
 
-            let addr = SyncArbiter::start(1, move || DBExecutor(pool.clone()));
+            let addr = SyncArbiter::start(1, move || DBExecutor(store.clone(), keyring.clone()));
             tx.send(addr).unwrap();
 
             sys.run();
@@ -128,20 +184,25 @@ impl TestSuit {
         rx.recv().unwrap()
     }
 
-    pub fn create_data(database_url: &str) -> Vec<Paste> {
-        use crate::models::{paste::NewPaste, schema::pastes::dsl::*};
+    pub fn create_data(config: &Config) -> Vec<Paste> {
+        use crate::common::{slug::generate as generate_slug, token};
+        use crate::models::{
+            paste::{NewPaste, Visibility},
+            schema::pastes::dsl::*,
+        };
         use std::time::SystemTime;
 
-        let conn = PgConnection::establish(database_url).unwrap();
-
+        let slug_length = config.paste.slug_length;
         let now = SystemTime::now();
+        let token_hash = token::hash(constant::TEST_EDIT_TOKEN);
         let paste_list = (1..10)
             .map(|n| {
                 (
                     "test title ".to_owned() + &n.to_string(),
                     "test body ".to_owned() + &n.to_string(),
+                    generate_slug(slug_length),
                 )
-            }).collect::<Vec<(_, _)>>();
+            }).collect::<Vec<(_, _, _)>>();
         let new_paste_list = (0..9)
             .map(|i| {
                 let paste = paste_list.get(i).unwrap();
@@ -150,16 +211,53 @@ impl TestSuit {
                     body: &paste.1,
                     created_at: &now,
                     modified_at: &now,
+                    expires_at: None,
+                    burn_after_read: false,
+                    slug: &paste.2,
+                    language: None,
+                    password_hash: None,
+                    cipher: None,
+                    nonce: None,
+                    key_id: None,
+                    data_key: None,
+                    visibility: Visibility::Public,
+                    access_token_hash: None,
+                    edit_token_hash: Some(token_hash.as_str()),
                 }
             }).collect::<Vec<_>>();
 
-        diesel::delete(pastes)
-            .execute(&conn)
-            .expect("fail to clear table");
+        let store: Box<PasteStore> = match config.database.kind {
+            DatabaseKind::Postgres => {
+                let conn = PgConnection::establish(&config.postgres.database_url()).unwrap();
+
+                diesel::delete(pastes)
+                    .execute(&conn)
+                    .expect("fail to clear table");
+
+                return diesel::insert_into(pastes)
+                    .values(&new_paste_list)
+                    .get_results(&conn)
+                    .expect("fail to insert test data");
+            }
+            DatabaseKind::Sqlite => {
+                let conn = SqliteConnection::establish(&config.sqlite.path).unwrap();
+
+                diesel::delete(sqlite::schema::paste_revisions::table)
+                    .execute(&conn)
+                    .expect("fail to clear table");
+                diesel::delete(sqlite::schema::pastes::table)
+                    .execute(&conn)
+                    .expect("fail to clear table");
+
+                Box::new(SqliteStore::new(sqlite::create_pool(&config.sqlite)))
+            }
+            DatabaseKind::Memory => Box::new(MemoryStore::default()),
+        };
 
-        diesel::insert_into(pastes)
-            .values(&new_paste_list)
-            .get_results(&conn)
-            .expect("fail to insert test data")
+        // stores other than postgres record a first revision along with the test data
+        new_paste_list
+            .iter()
+            .map(|new_paste| store.create(new_paste).unwrap().unwrap())
+            .collect()
     }
 }
@@ -1,17 +1,30 @@
 use actix_web::test::TestServer;
-use actix_web::{http::Method, App, HttpMessage};
+use actix_web::{
+    http::{header, Method},
+    App, HttpMessage,
+};
 use serde_json;
 
 use crate::apps::paste as paste_app;
-use crate::common::{constant::*, error::ResponseError};
-use crate::controllers::paste::{NewPaste, UpdatePaste};
-use crate::models::paste::Paste;
+use crate::common::{
+    config::DatabaseKind,
+    constant::*,
+    diff::{Diff, LineKind},
+    error::ResponseError,
+};
+use crate::controllers::paste::{NewPaste, PastePatch, UpdatePaste};
+use crate::models::{
+    paste::{CreatedPaste, ListedPaste, Paste, PasteListEnvelope, PastePage, Visibility},
+    revision::PasteRevision,
+};
 use crate::server::State;
+use crate::services::paste as paste_srv;
 use crate::tests::{constant::*, TEST_SUIT};
 
 fn create_app() -> App<State> {
     paste_app::create(State {
         db_chan: TEST_SUIT.executor(),
+        slug_length: TEST_SUIT.config().paste.slug_length,
     })
 }
 
@@ -19,6 +32,19 @@ fn init_server() -> TestServer {
     TestServer::with_factory(create_app)
 }
 
+fn fetch_page_by_title(srv: &mut TestServer, cursor: &str) -> PastePage {
+    let req = srv
+        .client(
+            Method::GET,
+            &format!("/pastes?limit=4&orderby_list=Title%3Aasc&cursor={}", cursor),
+        ).finish()
+        .unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    assert!(res.status().is_success());
+
+    parse_json!(srv, res, PastePage)
+}
+
 #[test]
 fn test_get_paste_by_id() {
     let _lock = TEST_SUIT.begin_isolated_test();
@@ -28,23 +54,50 @@ fn test_get_paste_by_id() {
     let mut srv = init_server();
 
     let req = srv
-        .client(Method::GET, &format!("/pastes/{}", paste.id))
+        .client(Method::GET, &format!("/pastes/{}", paste.slug))
         .finish()
         .unwrap();
 
     assert_res!(srv, req, Paste, |fetched_paste: Paste| {
-        assert_eq!(fetched_paste.id, paste.id);
+        assert_eq!(fetched_paste.slug, paste.slug);
         assert_eq!(fetched_paste.title, paste.title);
         assert_eq!(fetched_paste.body, paste.body);
     });
 }
 
+#[test]
+fn test_get_raw_paste() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let paste_list = TEST_SUIT.data();
+    let paste = paste_list.first().unwrap();
+
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::GET, &format!("/pastes/{}/raw", paste.slug))
+        .finish()
+        .unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    assert!(res.status().is_success());
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        CONTENT_TYPE_TEXT
+    );
+    assert_eq!(
+        res.headers().get("content-disposition").unwrap(),
+        "inline; filename=\"test_title_1.txt\""
+    );
+
+    let body = srv.execute(res.body()).unwrap();
+    assert_eq!(&body[..], paste.body.as_bytes());
+}
+
 #[test]
 fn test_get_paste_by_bad_id() {
     let mut srv = init_server();
 
     let req = srv
-        .client(Method::GET, &format!("/pastes/{}", "dddd"))
+        .client(Method::GET, &format!("/pastes/{}", "bad.id"))
         .finish()
         .unwrap();
 
@@ -136,7 +189,43 @@ fn test_get_paste_list_with_bad_cmp_created_at() {
         ).finish()
         .unwrap();
 
-    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_PARSE_TIME_COND_FAIL);
+    assert_res_err_msg!(
+        srv,
+        req,
+        400,
+        format!(
+            "{}: cmp_created_at: unknown operator `DD`, expected one of GT, EQ, LT, GE or LE",
+            ERR_MSG_PAYLOAD_PARSE_TIME_COND_FAIL
+        )
+    );
+
+    let req = srv
+        .client(Method::GET, "/pastes?cmp_created_at=-7d..tomorrow")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(
+        srv,
+        req,
+        400,
+        format!(
+            "{}: cmp_created_at: invalid range end `tomorrow`, {}",
+            ERR_MSG_PAYLOAD_PARSE_TIME_COND_FAIL, ERR_MSG_PAYLOAD_BAD_TIME
+        )
+    );
+
+    let req = srv
+        .client(Method::GET, "/pastes?cmp_created_at=2026-02-01..2026-01-01")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(
+        srv,
+        req,
+        400,
+        format!(
+            "{}: cmp_created_at: range start is after its end",
+            ERR_MSG_PAYLOAD_PARSE_TIME_COND_FAIL
+        )
+    );
 }
 
 #[test]
@@ -153,7 +242,53 @@ fn test_get_paste_list_with_bad_cmp_modified_at() {
         ).finish()
         .unwrap();
 
-    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_PARSE_TIME_COND_FAIL);
+    assert_res_err_msg!(
+        srv,
+        req,
+        400,
+        format!(
+            "{}: cmp_modified_at: unknown operator `DD`, expected one of GT, EQ, LT, GE or LE",
+            ERR_MSG_PAYLOAD_PARSE_TIME_COND_FAIL
+        )
+    );
+
+    let req = srv
+        .client(Method::GET, "/pastes?cmp_modified_at=GE%2C2026-01-01T00%3A00%3A00")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(
+        srv,
+        req,
+        400,
+        format!(
+            "{}: cmp_modified_at: invalid time `2026-01-01T00:00:00`, {}",
+            ERR_MSG_PAYLOAD_PARSE_TIME_COND_FAIL, ERR_MSG_PAYLOAD_BAD_TIME
+        )
+    );
+}
+
+#[test]
+fn test_get_paste_list_with_time_formats() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+
+    for cond in &[
+        "cmp_created_at=GE%2C2000-01-01T00%3A00%3A00Z",
+        "cmp_created_at=GT%2C-1h",
+        "cmp_modified_at=-1d..2100-01-01",
+    ] {
+        let req = srv
+            .client(Method::GET, &format!("/pastes?{}", cond))
+            .finish()
+            .unwrap();
+        assert_res!(srv, req, Vec<Paste>, |pastes: Vec<Paste>| assert_eq!(pastes.len(), 9));
+    }
+
+    let req = srv
+        .client(Method::GET, "/pastes?cmp_created_at=2000-01-01..2000-02-01")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, Vec<Paste>, |pastes: Vec<Paste>| assert!(pastes.is_empty()));
 }
 
 #[test]
@@ -173,6 +308,376 @@ fn test_get_paste_list_with_bad_orderby_list() {
     assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_PARSE_ORDERBY_FAIL);
 }
 
+#[test]
+fn test_get_paste_list_with_match_mode() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+    let assert_titles = |pastes: Vec<Paste>, titles: Vec<&str>| {
+        let mut found = pastes.iter().map(|paste| paste.title.as_str()).collect::<Vec<_>>();
+        found.sort();
+        assert_eq!(found, titles);
+    };
+
+    let req = srv
+        .client(Method::GET, "/pastes?title_pat=title%203&title_mode=contains")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, Vec<Paste>, |pastes| assert_titles(pastes, vec!["test title 3"]));
+
+    // wildcards in the pattern only match themselves
+    let req = srv
+        .client(Method::GET, "/pastes?title_pat=title_3&title_mode=contains")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, Vec<Paste>, |pastes| assert_titles(pastes, vec![]));
+
+    let req = srv
+        .client(Method::GET, "/pastes?body_pat=test%20body&body_mode=exact")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, Vec<Paste>, |pastes| assert_titles(pastes, vec![]));
+
+    let req = srv
+        .client(Method::GET, "/pastes?title_pat=title%20%5B12%5D%24&title_mode=regex")
+        .finish()
+        .unwrap();
+    let fuzzy_req = srv
+        .client(Method::GET, "/pastes?title_pat=titel%201&title_mode=fuzzy")
+        .finish()
+        .unwrap();
+
+    if let DatabaseKind::Sqlite = TEST_SUIT.config().database.kind {
+        assert_res_err!(srv, req, 400, |_| {});
+        assert_res_err!(srv, fuzzy_req, 400, |_| {});
+    } else {
+        assert_res!(srv, req, Vec<Paste>, |pastes| {
+            assert_titles(pastes, vec!["test title 1", "test title 2"])
+        });
+        assert_res!(srv, fuzzy_req, Vec<Paste>, |pastes| {
+            assert_titles(pastes, vec!["test title 1"])
+        });
+    }
+}
+
+#[test]
+fn test_get_paste_list_with_bad_match_mode() {
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::GET, "/pastes?title_pat=test&title_mode=suffix")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_BAD_MATCH_MODE);
+
+    let req = srv
+        .client(Method::GET, "/pastes?body_pat=%28&body_mode=regex")
+        .finish()
+        .unwrap();
+    assert_res_err!(srv, req, 400, |_| {});
+}
+
+#[test]
+fn test_get_paste_list_with_filter() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+
+    // title ~ "TITLE 1" or (body = "test body 2" and not created_at < 2000-01-01)
+    let req = srv
+        .client(
+            Method::GET,
+            "/pastes?filter=title%20~%20%22TITLE%201%22%20or%20\
+             %28body%20%3D%20%22test%20body%202%22%20and%20not%20created_at%20%3C%202000-01-01%29",
+        ).finish()
+        .unwrap();
+    assert_res!(srv, req, Vec<Paste>, |pastes: Vec<Paste>| {
+        let mut titles = pastes.iter().map(|paste| paste.title.as_str()).collect::<Vec<_>>();
+        titles.sort();
+        assert_eq!(titles, vec!["test title 1", "test title 2"]);
+    });
+
+    // pastes without a language are never of one
+    let req = srv
+        .client(Method::GET, "/pastes?filter=language%20%3D%20rust")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, Vec<Paste>, |pastes: Vec<Paste>| assert!(pastes.is_empty()));
+
+    let req = srv
+        .client(Method::GET, "/pastes?filter=language%20!%3D%20rust")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, Vec<Paste>, |pastes: Vec<Paste>| assert!(!pastes.is_empty()));
+}
+
+#[test]
+fn test_get_paste_list_with_bad_filter() {
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::GET, "/pastes?filter=title%20%3D")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(
+        srv,
+        req,
+        400,
+        format!(
+            "{}: expected a value, found the end at column 8",
+            ERR_MSG_PAYLOAD_BAD_FILTER
+        )
+    );
+}
+
+#[test]
+fn test_get_paste_list_by_cursor() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+    let titles = |page: &PastePage| {
+        page.items
+            .iter()
+            .map(|item| item.paste.title.to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    let first = fetch_page_by_title(&mut srv, "");
+    assert_eq!(
+        titles(&first),
+        vec!["test title 1", "test title 2", "test title 3", "test title 4"]
+    );
+    assert!(first.prev_cursor.is_none());
+
+    // pastes added before the current position shift nothing on later pages
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(NewPaste {
+            title: "test title 0".to_string(),
+            body: "test body 0".to_string(),
+            ttl: None,
+            burn_after_read: false,
+            slug: None,
+            language: None,
+            password: None,
+            encrypted: false,
+            cipher: None,
+            nonce: None,
+            visibility: None,
+        }).unwrap();
+    srv.execute(req.send()).unwrap();
+
+    let second = fetch_page_by_title(&mut srv, first.next_cursor.as_ref().unwrap());
+    assert_eq!(
+        titles(&second),
+        vec!["test title 5", "test title 6", "test title 7", "test title 8"]
+    );
+    assert!(second.prev_cursor.is_some());
+
+    let last = fetch_page_by_title(&mut srv, second.next_cursor.as_ref().unwrap());
+    assert_eq!(titles(&last), vec!["test title 9"]);
+    assert!(last.next_cursor.is_none());
+
+    let back = fetch_page_by_title(&mut srv, last.prev_cursor.as_ref().unwrap());
+    assert_eq!(titles(&back), titles(&second));
+    assert!(back.next_cursor.is_some());
+
+    let back = fetch_page_by_title(&mut srv, back.prev_cursor.as_ref().unwrap());
+    assert_eq!(
+        titles(&back),
+        vec!["test title 1", "test title 2", "test title 3", "test title 4"]
+    );
+    assert!(back.prev_cursor.is_some());
+}
+
+#[test]
+fn test_get_paste_list_with_envelope() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+
+    let req = srv
+        .client(
+            Method::GET,
+            "/pastes?title_pat=test&envelope=true&limit=4&offset=4",
+        ).finish()
+        .unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    assert!(res.status().is_success());
+
+    let links = res.headers()[header::LINK].to_str().unwrap().to_owned();
+    for (offset, rel) in vec![(0, "first"), (0, "prev"), (8, "next"), (8, "last")] {
+        assert!(links.split(", ").any(|link| {
+            link.contains(&format!("offset={}&", offset))
+                && link.ends_with(&format!("rel=\"{}\"", rel))
+        }));
+    }
+    assert!(links.contains("title_pat=test&"));
+
+    let envelope = parse_json!(srv, res, PasteListEnvelope);
+    assert_eq!(envelope.items.len(), 4);
+    assert_eq!(envelope.total, 9);
+    assert_eq!(envelope.limit, Some(4));
+    assert_eq!(envelope.offset, 4);
+    assert!(envelope.has_more);
+
+    let req = srv
+        .client(Method::GET, "/pastes?envelope=true&limit=4&offset=8")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, PasteListEnvelope, |envelope: PasteListEnvelope| {
+        assert_eq!(envelope.items.len(), 1);
+        assert_eq!(envelope.total, 9);
+        assert!(!envelope.has_more);
+    });
+}
+
+#[test]
+fn test_get_paste_list_with_bad_cursor() {
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::GET, "/pastes?cursor=bad")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_BAD_CURSOR);
+
+    let req = srv
+        .client(Method::GET, "/pastes?cursor=&offset=10")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_CURSOR_WITH_OFFSET);
+
+    let req = srv
+        .client(Method::GET, "/pastes?cursor=&q=test")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_CURSOR_WITH_QUERY);
+}
+
+#[test]
+fn test_get_paste_list_with_bad_paging() {
+    let mut srv = init_server();
+
+    for query in &[
+        "limit=0",
+        "limit=-1",
+        "limit=101",
+        "cursor=&limit=9223372036854775807",
+        "offset=-4",
+    ] {
+        let req = srv
+            .client(Method::GET, &format!("/pastes?{}", query))
+            .finish()
+            .unwrap();
+        assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_BAD_PAGING);
+    }
+}
+
+#[test]
+fn test_get_paste_list_without_limit() {
+    use futures::future::Future;
+    use std::time::SystemTime;
+
+    let _lock = TEST_SUIT.begin_isolated_test();
+    for n in 0..DEFAULT_PAGE_LIMIT {
+        sync_send!(
+            TEST_SUIT.executor(),
+            paste_srv::CreatePasteMsg {
+                title: format!("extra title {}", n),
+                body: "extra body".to_string(),
+                created_at: SystemTime::now(),
+                expires_at: None,
+                burn_after_read: false,
+                slug: None,
+                slug_length: TEST_SUIT.config().paste.slug_length,
+                language: None,
+                password: None,
+                encryption: None,
+                visibility: Visibility::Public,
+            }
+        );
+    }
+
+    let mut srv = init_server();
+
+    // more pastes are stored than fit on a page by default
+    let req = srv.client(Method::GET, "/pastes").finish().unwrap();
+    assert_res!(srv, req, Vec<Paste>, |pastes: Vec<Paste>| {
+        assert_eq!(pastes.len() as i64, DEFAULT_PAGE_LIMIT);
+    });
+
+    let req = srv
+        .client(Method::GET, "/pastes?envelope=true")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, PasteListEnvelope, |envelope: PasteListEnvelope| {
+        assert_eq!(envelope.limit, Some(DEFAULT_PAGE_LIMIT));
+        assert!(envelope.has_more);
+    });
+}
+
+#[test]
+fn test_search_paste_list() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+
+    for (title, body) in vec![
+        ("deploy notes", "remember to drain the nodes before the kubernetes rollout"),
+        ("kubernetes rollout", "kubectl rollout status deployment/web"),
+    ] {
+        let req = srv
+            .client(Method::POST, "/pastes")
+            .content_type(CONTENT_TYPE_JSON)
+            .json(NewPaste {
+                title: title.to_string(),
+                body: body.to_string(),
+                ttl: None,
+                burn_after_read: false,
+                slug: None,
+                language: None,
+                password: None,
+                encrypted: false,
+                cipher: None,
+                nonce: None,
+                visibility: None,
+            }).unwrap();
+        srv.execute(req.send()).unwrap();
+    }
+
+    let req = srv
+        .client(Method::GET, "/pastes?q=kubernetes%20rollout")
+        .finish()
+        .unwrap();
+
+    if let DatabaseKind::Postgres = TEST_SUIT.config().database.kind {
+        assert_res!(srv, req, Vec<ListedPaste>, |pastes: Vec<ListedPaste>| {
+            assert_eq!(pastes.len(), 2);
+            // matches in the title weigh more than in the body
+            assert_eq!(pastes[0].paste.title, "kubernetes rollout");
+            assert!(pastes[0].rank >= pastes[1].rank);
+            assert!(
+                pastes[1]
+                    .snippet
+                    .as_ref()
+                    .unwrap()
+                    .contains("<mark>kubernetes</mark> <mark>rollout</mark>")
+            );
+        });
+    } else {
+        assert_res_err!(srv, req, 400, |_| {});
+    }
+}
+
+#[test]
+fn test_get_paste_list_by_relevance_without_query() {
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::GET, "/pastes?orderby_list=Relevance%3Adecs")
+        .finish()
+        .unwrap();
+
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_RELEVANCE_WITHOUT_QUERY);
+}
+
 #[test]
 fn test_creat_paste() {
     let _lock = TEST_SUIT.begin_isolated_test();
@@ -185,86 +690,1227 @@ fn test_creat_paste() {
             serde_json::to_vec(&NewPaste {
                 title: "test new paste".to_string(),
                 body: "my new paste".to_string(),
+                ttl: None,
+                burn_after_read: false,
+                slug: None,
+                language: None,
+                password: None,
+                encrypted: false,
+                cipher: None,
+                nonce: None,
+                visibility: None,
             }).unwrap(),
         ).unwrap();
 
     assert_res!(srv, req, Paste, |created_paste: Paste| {
-        assert!(created_paste.id > 0);
+        assert_eq!(
+            created_paste.slug.len(),
+            TEST_SUIT.config().paste.slug_length
+        );
         assert_eq!(created_paste.title, "test new paste");
         assert_eq!(created_paste.body, "my new paste");
+        assert_eq!(created_paste.expires_at, None);
     });
 }
 
 #[test]
-fn test_create_paste_with_bad_payload() {
+fn test_create_paste_with_ttl() {
+    let _lock = TEST_SUIT.begin_isolated_test();
     let mut srv = init_server();
 
     let req = srv
         .client(Method::POST, "/pastes")
         .content_type(CONTENT_TYPE_JSON)
-        .body("{\"bad\": \"bad payload\"}")
-        .unwrap();
+        .json(NewPaste {
+            title: "test expiring paste".to_string(),
+            body: "gone soon".to_string(),
+            ttl: Some(60),
+            burn_after_read: false,
+            slug: None,
+            language: None,
+            password: None,
+            encrypted: false,
+            cipher: None,
+            nonce: None,
+            visibility: None,
+        }).unwrap();
 
-    assert_res_err!(srv, req, 400, |res: ResponseError| {
-        assert!(res.msg.contains("Json deserialize error"));
+    assert_res!(srv, req, Paste, |created_paste: Paste| {
+        let expires_at = created_paste.expires_at.unwrap();
+        assert!(expires_at > created_paste.created_at);
     });
 }
 
 #[test]
-fn test_update_paste() {
-    let _lock = TEST_SUIT.begin_isolated_test();
-    let paste_list = TEST_SUIT.data();
-    let paste = paste_list.first().unwrap();
-
+fn test_create_paste_with_bad_ttl() {
     let mut srv = init_server();
 
     let req = srv
-        .client(Method::POST, &format!("/pastes/{}", paste.id))
+        .client(Method::POST, "/pastes")
         .content_type(CONTENT_TYPE_JSON)
-        .json(UpdatePaste {
-            id: paste.id,
-            title: "test updated paste".to_string(),
-            body: "test updated ddd body".to_string(),
+        .json(NewPaste {
+            title: "test expiring paste".to_string(),
+            body: "gone soon".to_string(),
+            ttl: Some(0),
+            burn_after_read: false,
+            slug: None,
+            language: None,
+            password: None,
+            encrypted: false,
+            cipher: None,
+            nonce: None,
+            visibility: None,
         }).unwrap();
 
-    assert_res!(srv, req, Paste, |updated_paste: Paste| {
-        assert!(updated_paste.id == paste.id);
-        assert_eq!(updated_paste.title, "test updated paste");
-        assert_eq!(updated_paste.body, "test updated ddd body");
-    });
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_BAD_TTL);
 }
 
 #[test]
-fn test_update_paste_with_bad_payload() {
-    let paste_list = TEST_SUIT.data();
-    let paste = paste_list.first().unwrap();
+fn test_expired_paste_is_gone() {
+    use futures::future::Future;
+    use std::time::{Duration, SystemTime};
+
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let now = SystemTime::now();
+    let expired_paste = sync_send!(
+        TEST_SUIT.executor(),
+        paste_srv::CreatePasteMsg {
+            title: "test expired paste".to_string(),
+            body: "already gone".to_string(),
+            created_at: now - Duration::from_secs(120),
+            expires_at: Some(now - Duration::from_secs(60)),
+            burn_after_read: false,
+            slug: None,
+            slug_length: TEST_SUIT.config().paste.slug_length,
+            language: None,
+            password: None,
+            encryption: None,
+            visibility: Visibility::Public,
+        }
+    ).paste;
 
     let mut srv = init_server();
 
     let req = srv
-        .client(Method::POST, &format!("/pastes/{}", paste.id))
-        .content_type(CONTENT_TYPE_JSON)
-        .body("{\"id\": \"dddd\"}")
+        .client(Method::GET, &format!("/pastes/{}", expired_paste.slug))
+        .finish()
         .unwrap();
+    assert_res_err_msg!(srv, req, 404, ERR_MSG_DATA_NOT_FOUND);
 
-    assert_res_err!(srv, req, 400, |res: ResponseError| {
-        assert!(res.msg.contains("Json deserialize error"));
+    let req = srv.client(Method::GET, "/pastes").finish().unwrap();
+    assert_res!(srv, req, Vec<Paste>, |pastes: Vec<Paste>| {
+        assert!(pastes.iter().all(|paste| paste.slug != expired_paste.slug));
     });
+
+    let purged = sync_send!(
+        TEST_SUIT.executor(),
+        paste_srv::PurgeExpiredPastesMsg {
+            now: SystemTime::now(),
+            batch_size: 1,
+        }
+    );
+    assert_eq!(purged, 1);
 }
 
 #[test]
-fn test_del_paste_by_id() {
+fn test_burn_after_read_paste() {
     let _lock = TEST_SUIT.begin_isolated_test();
-    let paste_list = TEST_SUIT.data();
-    let paste = paste_list.first().unwrap();
-
     let mut srv = init_server();
 
     let req = srv
-        .client(Method::DELETE, &format!("/pastes/{}", paste.id))
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(NewPaste {
+            title: "test burning paste".to_string(),
+            body: "read me once".to_string(),
+            ttl: None,
+            burn_after_read: true,
+            slug: None,
+            language: None,
+            password: None,
+            encrypted: false,
+            cipher: None,
+            nonce: None,
+            visibility: None,
+        }).unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    let created_paste = parse_json!(srv, res, Paste);
+    assert!(created_paste.burn_after_read);
+
+    let req = srv.client(Method::GET, "/pastes").finish().unwrap();
+    assert_res!(srv, req, Vec<Paste>, |pastes: Vec<Paste>| {
+        assert!(pastes.iter().all(|paste| paste.slug != created_paste.slug));
+    });
+
+    // the only read must not be spent on a 304 without a body
+    let req = srv
+        .client(Method::GET, &format!("/pastes/{}", created_paste.slug))
+        .header(header::IF_NONE_MATCH, "*")
         .finish()
         .unwrap();
+    assert_res!(srv, req, Paste, |fetched_paste: Paste| {
+        assert_eq!(fetched_paste.body, "read me once");
+    });
 
+    let req = srv
+        .client(Method::GET, &format!("/pastes/{}", created_paste.slug))
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 404, ERR_MSG_DATA_NOT_FOUND);
+}
+
+#[test]
+fn test_password_protected_paste() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(NewPaste {
+            title: "test protected paste".to_string(),
+            body: "for your eyes only".to_string(),
+            ttl: None,
+            burn_after_read: false,
+            slug: Some("protected".to_string()),
+            language: None,
+            password: Some("hunter2".to_string()),
+            encrypted: false,
+            cipher: None,
+            nonce: None,
+            visibility: None,
+        }).unwrap();
+    assert_res!(srv, req, Paste, |created_paste: Paste| {
+        assert!(created_paste.is_protected());
+    });
+
+    let req = srv
+        .client(Method::GET, "/pastes/protected")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 401, ERR_MSG_PASSWORD_REQUIRED);
+    let req = srv
+        .client(Method::GET, "/pastes/protected")
+        .header(HEADER_PASTE_PASSWORD, "hunter3")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 401, ERR_MSG_PASSWORD_REQUIRED);
+
+    let req = srv
+        .client(Method::GET, "/pastes/protected")
+        .header(HEADER_PASTE_PASSWORD, "hunter2")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, Paste, |paste: Paste| {
+        assert_eq!(paste.body, "for your eyes only");
+    });
+
+    let req = srv
+        .client(Method::POST, "/pastes/protected/raw")
+        .form(&[("password", "hunter2")])
+        .unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    assert!(res.status().is_success());
+    let body = srv.execute(res.body()).unwrap();
+    assert_eq!(&body[..], b"for your eyes only");
+
+    // a charset parameter on the form must not turn the read into an update
+    let req = srv
+        .client(Method::POST, "/pastes/protected")
+        .content_type("application/x-www-form-urlencoded; charset=UTF-8")
+        .body("password=hunter2")
+        .unwrap();
+    assert_res!(srv, req, Paste, |paste: Paste| {
+        assert_eq!(paste.title, "test protected paste");
+        assert_eq!(paste.body, "for your eyes only");
+    });
+
+    // listings show that the paste is there, not what it says
+    let req = srv
+        .client(Method::GET, "/pastes?title_pat=protected")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, Vec<Paste>, |paste_list: Vec<Paste>| {
+        assert_eq!(paste_list.len(), 1);
+        assert!(paste_list[0].is_protected());
+        assert_eq!(paste_list[0].body, "");
+    });
+    let req = srv
+        .client(Method::GET, "/pastes?body_pat=eyes")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, Vec<Paste>, |paste_list: Vec<Paste>| {
+        assert!(paste_list.is_empty());
+    });
+
+    let req = srv
+        .client(Method::GET, "/pastes/protected/revisions")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 401, ERR_MSG_PASSWORD_REQUIRED);
+}
+
+#[test]
+fn test_paste_visibility() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+    let mut create = |slug: &str, visibility: Visibility| {
+        let req = srv
+            .client(Method::POST, "/pastes")
+            .content_type(CONTENT_TYPE_JSON)
+            .json(NewPaste {
+                title: "test hidden paste".to_string(),
+                body: "not for everyone".to_string(),
+                ttl: None,
+                burn_after_read: false,
+                slug: Some(slug.to_string()),
+                language: None,
+                password: None,
+                encrypted: false,
+                cipher: None,
+                nonce: None,
+                visibility: Some(visibility),
+            }).unwrap();
+        let res = srv.execute(req.send()).unwrap();
+        assert!(res.status().is_success());
+
+        parse_json!(srv, res, CreatedPaste)
+    };
+
+    let unlisted = create("unlisted", Visibility::Unlisted);
+    assert_eq!(unlisted.paste.visibility, Visibility::Unlisted);
+    assert!(unlisted.access_token.is_none());
+    let private = create("private", Visibility::Private);
+    assert_eq!(private.paste.visibility, Visibility::Private);
+    let token = private.access_token.unwrap();
+
+    // neither is listed, nor counted
+    let req = srv
+        .client(Method::GET, "/pastes?title_pat=test%20hidden&envelope=true")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, PasteListEnvelope, |envelope: PasteListEnvelope| {
+        assert!(envelope.items.is_empty());
+        assert_eq!(envelope.total, 0);
+    });
+
+    let req = srv.client(Method::GET, "/pastes/unlisted").finish().unwrap();
+    assert_res!(srv, req, Paste, |paste: Paste| {
+        assert_eq!(paste.body, "not for everyone");
+    });
+
+    // private pastes look missing to anyone without the token
+    let req = srv.client(Method::GET, "/pastes/private").finish().unwrap();
+    assert_res_err_msg!(srv, req, 404, ERR_MSG_DATA_NOT_FOUND);
+    let req = srv
+        .client(Method::GET, "/pastes/private")
+        .header(HEADER_PASTE_TOKEN, "not the token")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 404, ERR_MSG_DATA_NOT_FOUND);
+    let req = srv
+        .client(Method::GET, "/pastes/private/revisions")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 404, ERR_MSG_DATA_NOT_FOUND);
+    let req = srv
+        .client(Method::DELETE, "/pastes/private")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 404, ERR_MSG_DATA_NOT_FOUND);
+
+    let req = srv
+        .client(Method::GET, "/pastes/private")
+        .header(HEADER_PASTE_TOKEN, token.as_str())
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, Paste, |paste: Paste| {
+        assert_eq!(paste.body, "not for everyone");
+    });
+}
+
+#[test]
+fn test_create_paste_with_bad_password() {
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(NewPaste {
+            title: "test protected paste".to_string(),
+            body: "for your eyes only".to_string(),
+            ttl: None,
+            burn_after_read: false,
+            slug: None,
+            language: None,
+            password: Some("".to_string()),
+            encrypted: false,
+            cipher: None,
+            nonce: None,
+            visibility: None,
+        }).unwrap();
+
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_BAD_PASSWORD);
+}
+
+#[test]
+fn test_client_encrypted_paste() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+    // 12 zero bytes and 24 bytes of ciphertext with its tag, never decrypted here
+    let nonce = "AAAAAAAAAAAAAAAA";
+    let ciphertext = "2wN5OZmhwBeCVK6TAwgZ5oYvw3Mcq1Tp";
+
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(NewPaste {
+            title: "test encrypted paste".to_string(),
+            body: ciphertext.to_string(),
+            ttl: None,
+            burn_after_read: false,
+            slug: Some("encrypted".to_string()),
+            language: None,
+            password: None,
+            encrypted: true,
+            cipher: None,
+            nonce: Some(nonce.to_string()),
+            visibility: None,
+        }).unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    assert!(res.status().is_success());
+    let created = parse_json!(srv, res, CreatedPaste);
+    assert_eq!(created.paste.cipher.unwrap(), CIPHER_AES_GCM);
+    assert_eq!(created.paste.nonce.unwrap(), nonce);
+    assert_eq!(created.paste.body, ciphertext);
+    assert_eq!(created.paste.language, None);
+
+    let req = srv
+        .client(Method::GET, "/pastes/encrypted/highlighted")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 422, ERR_MSG_ENCRYPTED);
+    let req = srv
+        .client(Method::GET, "/pastes/encrypted/diff?from=1&to=1")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 422, ERR_MSG_ENCRYPTED);
+    let req = srv
+        .client(Method::GET, "/pastes/diff?a=encrypted&b=encrypted")
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 422, ERR_MSG_ENCRYPTED);
+
+    let req = srv
+        .client(Method::GET, "/pastes?body_pat=2wN5")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, Vec<Paste>, |paste_list: Vec<Paste>| {
+        assert!(paste_list.is_empty());
+    });
+
+    // the title can change, the body cannot without a new nonce
+    let req = srv
+        .client(Method::PATCH, "/pastes/encrypted")
+        .header(HEADER_PASTE_EDIT_TOKEN, created.edit_token.as_str())
+        .json(PastePatch {
+            title: Some(Some("test renamed paste".to_string())),
+            ..Default::default()
+        }).unwrap();
+    assert_res!(srv, req, Paste, |paste: Paste| {
+        assert_eq!(paste.title, "test renamed paste");
+        assert_eq!(paste.body, ciphertext);
+    });
+    let req = srv
+        .client(Method::PATCH, "/pastes/encrypted")
+        .header(HEADER_PASTE_EDIT_TOKEN, created.edit_token.as_str())
+        .json(PastePatch {
+            body: Some(Some("AAAAAAAAAAAAAAAAAAAAAAAA".to_string())),
+            ..Default::default()
+        }).unwrap();
+    assert_res_err_msg!(srv, req, 422, ERR_MSG_ENCRYPTED);
+
+    let req = srv
+        .client(Method::GET, "/pastes/encrypted/decrypt")
+        .finish()
+        .unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    assert!(res.status().is_success());
+    assert_eq!(res.headers().get("content-type").unwrap(), CONTENT_TYPE_HTML);
+}
+
+#[test]
+fn test_create_paste_with_bad_encryption() {
+    let mut srv = init_server();
+    let new_paste = |body: &str, encrypted, cipher: Option<&str>, nonce: Option<&str>| NewPaste {
+        title: "test encrypted paste".to_string(),
+        body: body.to_string(),
+        ttl: None,
+        burn_after_read: false,
+        slug: None,
+        language: None,
+        password: None,
+        encrypted,
+        cipher: cipher.map(str::to_owned),
+        nonce: nonce.map(str::to_owned),
+        visibility: None,
+    };
+    let ciphertext = "2wN5OZmhwBeCVK6TAwgZ5oYvw3Mcq1Tp";
+
+    for bad_paste in vec![
+        new_paste(ciphertext, true, None, None),
+        new_paste(ciphertext, true, None, Some("AAAA")),
+        new_paste(ciphertext, true, Some("aes-128-cbc"), Some("AAAAAAAAAAAAAAAA")),
+        new_paste("not ciphertext", true, None, Some("AAAAAAAAAAAAAAAA")),
+        new_paste("plain text", false, None, Some("AAAAAAAAAAAAAAAA")),
+    ] {
+        let req = srv
+            .client(Method::POST, "/pastes")
+            .content_type(CONTENT_TYPE_JSON)
+            .json(bad_paste)
+            .unwrap();
+        assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_BAD_ENCRYPTION);
+    }
+}
+
+#[test]
+fn test_create_paste_with_custom_slug() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+    let new_paste = || NewPaste {
+        title: "deploy runbook".to_string(),
+        body: "step 1: deploy".to_string(),
+        ttl: None,
+        burn_after_read: false,
+        slug: Some("deploy-runbook".to_string()),
+        language: None,
+        password: None,
+        encrypted: false,
+        cipher: None,
+        nonce: None,
+        visibility: None,
+    };
+
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(new_paste())
+        .unwrap();
+    assert_res!(srv, req, Paste, |created_paste: Paste| {
+        assert_eq!(created_paste.slug, "deploy-runbook");
+    });
+
+    let req = srv
+        .client(Method::GET, "/pastes/deploy-runbook")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, Paste, |fetched_paste: Paste| {
+        assert_eq!(fetched_paste.body, "step 1: deploy");
+    });
+
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(new_paste())
+        .unwrap();
+    assert_res_err_msg!(srv, req, 409, ERR_MSG_SLUG_CONFLICT);
+}
+
+#[test]
+fn test_create_paste_with_bad_custom_slug() {
+    let mut srv = init_server();
+    let new_paste = |custom_slug: &str| NewPaste {
+        title: "deploy runbook".to_string(),
+        body: "step 1: deploy".to_string(),
+        ttl: None,
+        burn_after_read: false,
+        slug: Some(custom_slug.to_string()),
+        language: None,
+        password: None,
+        encrypted: false,
+        cipher: None,
+        nonce: None,
+        visibility: None,
+    };
+
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(new_paste("-deploy runbook"))
+        .unwrap();
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_BAD_SLUG);
+
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(new_paste("Diff"))
+        .unwrap();
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_RESERVED_SLUG);
+}
+
+#[test]
+fn test_paste_language() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(NewPaste {
+            title: "main.rs".to_string(),
+            body: "fn main() {}".to_string(),
+            ttl: None,
+            burn_after_read: false,
+            slug: None,
+            language: None,
+            password: None,
+            encrypted: false,
+            cipher: None,
+            nonce: None,
+            visibility: None,
+        }).unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    let created_paste = parse_json!(srv, res, Paste);
+    assert_eq!(created_paste.language, Some("rust".to_string()));
+
+    let req = srv
+        .client(Method::GET, "/pastes?language=rust")
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, Vec<Paste>, |pastes: Vec<Paste>| {
+        assert_eq!(pastes.len(), 1);
+        assert_eq!(pastes[0].slug, created_paste.slug);
+    });
+
+    let req = srv
+        .client(
+            Method::GET,
+            &format!("/pastes/{}/highlighted", created_paste.slug),
+        ).finish()
+        .unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    assert!(res.status().is_success());
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        CONTENT_TYPE_HTML
+    );
+    let html = srv.execute(res.body()).unwrap();
+    assert!(
+        String::from_utf8_lossy(&html).contains("<span class=\"hl-keyword\">fn</span> main")
+    );
+}
+
+#[test]
+fn test_create_paste_with_bad_language() {
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(NewPaste {
+            title: "main.rs".to_string(),
+            body: "fn main() {}".to_string(),
+            ttl: None,
+            burn_after_read: false,
+            slug: None,
+            language: Some("<script>".to_string()),
+            password: None,
+            encrypted: false,
+            cipher: None,
+            nonce: None,
+            visibility: None,
+        }).unwrap();
+
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_BAD_LANGUAGE);
+}
+
+#[test]
+fn test_create_paste_from_raw_body() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::POST, "/pastes?ttl=60")
+        .header(HEADER_PASTE_TITLE, "build.log")
+        .header("accept", CONTENT_TYPE_TEXT)
+        .content_type("application/octet-stream")
+        .body("line 1\nline 2\n")
+        .unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    assert!(res.status().is_success());
+
+    let body = srv.execute(res.body()).unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.ends_with("\n"));
+    let mut lines = body.lines();
+    let paste_url = lines.next().unwrap();
+    assert!(paste_url.starts_with("http://"));
+    assert!(lines.next().unwrap().starts_with("edit_token: "));
+    let slug = paste_url.rsplit('/').next().unwrap();
+
+    let req = srv
+        .client(Method::GET, &format!("/pastes/{}", slug))
+        .finish()
+        .unwrap();
+    assert_res!(srv, req, Paste, |fetched_paste: Paste| {
+        assert_eq!(fetched_paste.title, "build.log");
+        assert_eq!(fetched_paste.body, "line 1\nline 2\n");
+        assert!(fetched_paste.expires_at.is_some());
+    });
+
+    // title may come from query string as well, reply is json by default
+    let req = srv
+        .client(Method::POST, "/pastes?title=notes")
+        .content_type("text/plain")
+        .body("some notes")
+        .unwrap();
+    assert_res!(srv, req, Paste, |created_paste: Paste| {
+        assert_eq!(created_paste.title, "notes");
+        assert_eq!(created_paste.body, "some notes");
+    });
+}
+
+#[test]
+fn test_create_paste_from_bad_raw_body() {
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type("application/octet-stream")
+        .body(vec![0xffu8, 0xfe, 0xfd])
+        .unwrap();
+
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_NOT_UTF8);
+
+    let req = srv
+        .client(Method::POST, "/pastes?ttl=soon")
+        .content_type("text/plain")
+        .body("some notes")
+        .unwrap();
+
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_BAD_PARAMS);
+}
+
+#[test]
+fn test_create_paste_ignores_query_for_json() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+
+    // options of json creates are in the payload, the query string is left alone
+    let req = srv
+        .client(Method::POST, "/pastes?ttl=soon&utm_source=feed")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(NewPaste {
+            title: "test title".to_string(),
+            body: "test body".to_string(),
+            ttl: None,
+            burn_after_read: false,
+            slug: None,
+            language: None,
+            password: None,
+            encrypted: false,
+            cipher: None,
+            nonce: None,
+            visibility: None,
+        }).unwrap();
+    assert_res!(srv, req, Paste, |created_paste: Paste| {
+        assert_eq!(created_paste.title, "test title");
+        assert!(created_paste.expires_at.is_none());
+    });
+}
+
+#[test]
+fn test_create_paste_with_bad_payload() {
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .body("{\"bad\": \"bad payload\"}")
+        .unwrap();
+
+    assert_res_err!(srv, req, 400, |res: ResponseError| {
+        assert!(res.msg.contains("Json deserialize error"));
+    });
+}
+
+#[test]
+fn test_update_paste() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let paste_list = TEST_SUIT.data();
+    let paste = paste_list.first().unwrap();
+
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::POST, &format!("/pastes/{}", paste.slug))
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .content_type(CONTENT_TYPE_JSON)
+        .json(UpdatePaste {
+            title: "test updated paste".to_string(),
+            body: "test updated ddd body".to_string(),
+            language: None,
+        }).unwrap();
+
+    assert_res!(srv, req, Paste, |updated_paste: Paste| {
+        assert_eq!(updated_paste.slug, paste.slug);
+        assert_eq!(updated_paste.title, "test updated paste");
+        assert_eq!(updated_paste.body, "test updated ddd body");
+    });
+}
+
+#[test]
+fn test_paste_etag() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let paste_list = TEST_SUIT.data();
+    let paste = paste_list.first().unwrap();
+    let url = format!("/pastes/{}", paste.slug);
+    let etag = format!("\"{}\"", paste.version);
+
+    let mut srv = init_server();
+
+    let req = srv.client(Method::GET, &url).finish().unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    assert!(res.status().is_success());
+    assert_eq!(res.headers().get(header::ETAG).unwrap(), etag.as_str());
+
+    let req = srv
+        .client(Method::GET, &url)
+        .header(header::IF_NONE_MATCH, format!("\"0\", W/{}", etag))
+        .finish()
+        .unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    assert_eq!(res.status().as_u16(), 304);
+
+    let update = || UpdatePaste {
+        title: "test updated paste".to_string(),
+        body: "test updated body".to_string(),
+        language: None,
+    };
+    let req = srv
+        .client(Method::POST, &url)
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .header(header::IF_MATCH, etag.as_str())
+        .json(update())
+        .unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    assert!(res.status().is_success());
+    let new_etag = res.headers().get(header::ETAG).unwrap().to_str().unwrap().to_owned();
+    assert_ne!(new_etag, etag);
+
+    // the paste has moved on since `etag` was fetched
+    let req = srv
+        .client(Method::POST, &url)
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .header(header::IF_MATCH, etag.as_str())
+        .json(update())
+        .unwrap();
+    assert_res_err_msg!(srv, req, 412, ERR_MSG_PRECONDITION_FAILED);
+
+    let req = srv
+        .client(Method::PATCH, &url)
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .header(header::IF_MATCH, format!("W/{}", new_etag))
+        .json(PastePatch::default())
+        .unwrap();
+    assert_res_err_msg!(srv, req, 412, ERR_MSG_PRECONDITION_FAILED);
+
+    let req = srv
+        .client(Method::DELETE, &url)
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .header(header::IF_MATCH, etag.as_str())
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 412, ERR_MSG_PRECONDITION_FAILED);
+
+    let req = srv
+        .client(Method::DELETE, &url)
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .header(header::IF_MATCH, new_etag.as_str())
+        .finish()
+        .unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    assert!(res.status().is_success());
+}
+
+#[test]
+fn test_patch_paste() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let paste_list = TEST_SUIT.data();
+    let paste = paste_list.first().unwrap();
+
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::PATCH, &format!("/pastes/{}", paste.slug))
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .content_type(CONTENT_TYPE_MERGE_PATCH)
+        .body("{\"title\": \"main.rs\"}")
+        .unwrap();
+    assert_res!(srv, req, Paste, |patched_paste: Paste| {
+        assert_eq!(patched_paste.slug, paste.slug);
+        assert_eq!(patched_paste.title, "main.rs");
+        assert_eq!(patched_paste.body, paste.body);
+        assert_eq!(patched_paste.language, Some("rust".to_owned()));
+    });
+
+    // the language sticks until it is removed and detected again
+    let req = srv
+        .client(Method::PATCH, &format!("/pastes/{}", paste.slug))
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .json(PastePatch {
+            title: Some(Some("notes".to_owned())),
+            ..Default::default()
+        }).unwrap();
+    assert_res!(srv, req, Paste, |patched_paste: Paste| {
+        assert_eq!(patched_paste.language, Some("rust".to_owned()));
+    });
+
+    let req = srv
+        .client(Method::PATCH, &format!("/pastes/{}", paste.slug))
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .content_type(CONTENT_TYPE_MERGE_PATCH)
+        .body("{\"body\": \"patched body\", \"language\": null}")
+        .unwrap();
+    assert_res!(srv, req, Paste, |patched_paste: Paste| {
+        assert_eq!(patched_paste.title, "notes");
+        assert_eq!(patched_paste.body, "patched body");
+        assert_eq!(patched_paste.language, None);
+    });
+}
+
+#[test]
+fn test_patch_paste_with_bad_payload() {
+    let paste_list = TEST_SUIT.data();
+    let paste = paste_list.first().unwrap();
+
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::PATCH, &format!("/pastes/{}", paste.slug))
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .content_type(CONTENT_TYPE_MERGE_PATCH)
+        .body("{\"title\": null}")
+        .unwrap();
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_PATCH_REMOVES_REQUIRED);
+
+    let req = srv
+        .client(Method::PATCH, &format!("/pastes/{}", paste.slug))
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .content_type(CONTENT_TYPE_MERGE_PATCH)
+        .body("{\"id\": \"dddd\"}")
+        .unwrap();
+    assert_res_err!(srv, req, 400, |res: ResponseError| {
+        assert!(res.msg.contains("unknown field `id`"));
+    });
+
+    let req = srv
+        .client(Method::PATCH, &format!("/pastes/{}", paste.slug))
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .content_type("application/json-patch+json")
+        .body("[{\"op\": \"replace\", \"path\": \"/title\", \"value\": \"x\"}]")
+        .unwrap();
+    assert_res_err_msg!(srv, req, 415, ERR_MSG_PAYLOAD_BAD_PATCH_TYPE);
+}
+
+#[test]
+fn test_paste_revisions() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(NewPaste {
+            title: "test revised paste".to_string(),
+            body: "first draft".to_string(),
+            ttl: None,
+            burn_after_read: false,
+            slug: None,
+            language: None,
+            password: None,
+            encrypted: false,
+            cipher: None,
+            nonce: None,
+            visibility: None,
+        }).unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    let created = parse_json!(srv, res, CreatedPaste);
+    let created_paste = created.paste;
+
+    let req = srv
+        .client(Method::POST, &format!("/pastes/{}", created_paste.slug))
+        .header(header::AUTHORIZATION, format!("Bearer {}", created.edit_token))
+        .content_type(CONTENT_TYPE_JSON)
+        .json(UpdatePaste {
+            title: "test revised paste".to_string(),
+            body: "second draft".to_string(),
+            language: None,
+        }).unwrap();
+    srv.execute(req.send()).unwrap();
+
+    let req = srv
+        .client(
+            Method::GET,
+            &format!("/pastes/{}/revisions", created_paste.slug),
+        ).finish()
+        .unwrap();
+    assert_res!(srv, req, Vec<PasteRevision>, |revisions: Vec<PasteRevision>| {
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].revision, 1);
+        assert_eq!(revisions[0].body, "first draft");
+        assert_eq!(revisions[1].revision, 2);
+        assert_eq!(revisions[1].body, "second draft");
+    });
+
+    let req = srv
+        .client(
+            Method::GET,
+            &format!("/pastes/{}/revisions/1", created_paste.slug),
+        ).finish()
+        .unwrap();
+    assert_res!(srv, req, PasteRevision, |revision: PasteRevision| {
+        assert_eq!(revision.body, "first draft");
+    });
+
+    let req = srv
+        .client(
+            Method::GET,
+            &format!("/pastes/{}/revisions/3", created_paste.slug),
+        ).finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 404, ERR_MSG_DATA_NOT_FOUND);
+
+    let req = srv
+        .client(
+            Method::GET,
+            &format!("/pastes/{}/revisions/0", created_paste.slug),
+        ).finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_BAD_ID);
+}
+
+#[test]
+fn test_diff_paste_revisions() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(NewPaste {
+            title: "test diffed paste".to_string(),
+            body: "line 1\nline 2\nline 3".to_string(),
+            ttl: None,
+            burn_after_read: false,
+            slug: None,
+            language: None,
+            password: None,
+            encrypted: false,
+            cipher: None,
+            nonce: None,
+            visibility: None,
+        }).unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    let created = parse_json!(srv, res, CreatedPaste);
+    let created_paste = created.paste;
+
+    let req = srv
+        .client(Method::POST, &format!("/pastes/{}", created_paste.slug))
+        .header(HEADER_PASTE_EDIT_TOKEN, created.edit_token.as_str())
+        .content_type(CONTENT_TYPE_JSON)
+        .json(UpdatePaste {
+            title: "test diffed paste".to_string(),
+            body: "line 1\nline two\nline 3".to_string(),
+            language: None,
+        }).unwrap();
+    srv.execute(req.send()).unwrap();
+
+    let req = srv
+        .client(
+            Method::GET,
+            &format!("/pastes/{}/diff?from=1&to=2", created_paste.slug),
+        ).finish()
+        .unwrap();
+    assert_res!(srv, req, Diff, |diff: Diff| {
+        assert_eq!(diff.hunks.len(), 1);
+        assert_eq!(
+            diff.unified,
+            format!(
+                "--- {0}@1\n+++ {0}@2\n@@ -1,3 +1,3 @@\n line 1\n-line 2\n+line two\n line 3\n",
+                created_paste.slug
+            )
+        );
+    });
+
+    let req = srv
+        .client(
+            Method::GET,
+            &format!("/pastes/{}/diff?from=1", created_paste.slug),
+        ).finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_BAD_DIFF_REVISIONS);
+}
+
+#[test]
+fn test_diff_pastes() {
+    use futures::future::Future;
+    use std::time::SystemTime;
+
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let paste_list = TEST_SUIT.data();
+    let (a, b) = (&paste_list[0], &paste_list[1]);
+
+    let mut srv = init_server();
+
+    let req = srv
+        .client(
+            Method::GET,
+            &format!("/pastes/diff?a={}&b={}", a.slug, b.slug),
+        ).finish()
+        .unwrap();
+    assert_res!(srv, req, Diff, |diff: Diff| {
+        assert_eq!(diff.from, a.slug);
+        assert_eq!(diff.to, b.slug);
+        assert_eq!(diff.hunks[0].lines[0].kind, LineKind::Del);
+        assert_eq!(diff.hunks[0].lines[0].text, a.body);
+        assert_eq!(diff.hunks[0].lines[1].kind, LineKind::Add);
+        assert_eq!(diff.hunks[0].lines[1].text, b.body);
+    });
+
+    let req = srv
+        .client(Method::GET, &format!("/pastes/diff?a={}", a.slug))
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_BAD_DIFF_PASTES);
+
+    // a diff takes memory in proportion to the line counts of both sides multiplied
+    let large = sync_send!(
+        TEST_SUIT.executor(),
+        paste_srv::CreatePasteMsg {
+            title: "test large paste".to_string(),
+            body: "line\n".repeat(MAX_DIFF_LINES + 1),
+            created_at: SystemTime::now(),
+            expires_at: None,
+            burn_after_read: false,
+            slug: None,
+            slug_length: TEST_SUIT.config().paste.slug_length,
+            language: None,
+            password: None,
+            encryption: None,
+            visibility: Visibility::Public,
+        }
+    ).paste;
+    let req = srv
+        .client(
+            Method::GET,
+            &format!("/pastes/diff?a={}&b={}", a.slug, large.slug),
+        ).finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 400, ERR_MSG_PAYLOAD_DIFF_TOO_LARGE);
+}
+
+#[test]
+fn test_update_paste_with_bad_payload() {
+    let paste_list = TEST_SUIT.data();
+    let paste = paste_list.first().unwrap();
+
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::POST, &format!("/pastes/{}", paste.slug))
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .content_type(CONTENT_TYPE_JSON)
+        .body("{\"id\": \"dddd\"}")
+        .unwrap();
+
+    assert_res_err!(srv, req, 400, |res: ResponseError| {
+        assert!(res.msg.contains("Json deserialize error"));
+    });
+}
+
+#[test]
+fn test_del_paste_by_id() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let paste_list = TEST_SUIT.data();
+    let paste = paste_list.first().unwrap();
+
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::DELETE, &format!("/pastes/{}", paste.slug))
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .finish()
+        .unwrap();
+
+    assert_res!(srv, req, String, |res: String| {
+        assert_eq!(res, "ok");
+    });
+}
+
+#[test]
+fn test_edit_token() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let mut srv = init_server();
+
+    let req = srv
+        .client(Method::POST, "/pastes")
+        .content_type(CONTENT_TYPE_JSON)
+        .json(NewPaste {
+            title: "test guarded paste".to_string(),
+            body: "hands off".to_string(),
+            ttl: None,
+            burn_after_read: false,
+            slug: Some("guarded".to_string()),
+            language: None,
+            password: None,
+            encrypted: false,
+            cipher: None,
+            nonce: None,
+            visibility: None,
+        }).unwrap();
+    let res = srv.execute(req.send()).unwrap();
+    assert!(res.status().is_success());
+    let edit_token = parse_json!(srv, res, CreatedPaste).edit_token;
+    let update = || UpdatePaste {
+        title: "test guarded paste".to_string(),
+        body: "hands on".to_string(),
+        language: None,
+    };
+
+    let req = srv
+        .client(Method::POST, "/pastes/guarded")
+        .json(update())
+        .unwrap();
+    assert_res_err_msg!(srv, req, 403, ERR_MSG_EDIT_TOKEN_REQUIRED);
+    let req = srv
+        .client(Method::PATCH, "/pastes/guarded")
+        .header(header::AUTHORIZATION, "Bearer not-the-token")
+        .json(PastePatch::default())
+        .unwrap();
+    assert_res_err_msg!(srv, req, 403, ERR_MSG_EDIT_TOKEN_REQUIRED);
+    // tokens of other pastes open nothing here
+    let req = srv
+        .client(Method::DELETE, "/pastes/guarded")
+        .header(HEADER_PASTE_EDIT_TOKEN, TEST_EDIT_TOKEN)
+        .finish()
+        .unwrap();
+    assert_res_err_msg!(srv, req, 403, ERR_MSG_EDIT_TOKEN_REQUIRED);
+
+    let req = srv
+        .client(Method::POST, "/pastes/guarded")
+        .header(header::AUTHORIZATION, format!("Bearer {}", edit_token))
+        .json(update())
+        .unwrap();
+    assert_res!(srv, req, Paste, |paste: Paste| {
+        assert_eq!(paste.body, "hands on");
+    });
+
+    let req = srv
+        .client(Method::DELETE, "/pastes/guarded")
+        .header(HEADER_PASTE_EDIT_TOKEN, edit_token.as_str())
+        .finish()
+        .unwrap();
     assert_res!(srv, req, String, |res: String| {
         assert_eq!(res, "ok");
     });
@@ -275,7 +1921,7 @@ fn test_del_paste_by_bad_id() {
     let mut srv = init_server();
 
     let req = srv
-        .client(Method::DELETE, &format!("/pastes/{}", "dddd"))
+        .client(Method::DELETE, &format!("/pastes/{}", "bad.id"))
         .finish()
         .unwrap();
 
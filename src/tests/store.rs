@@ -0,0 +1,269 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::common::{error::ServerError, token};
+use crate::models::{
+    list::{CmpOp, Item, MatchMode, Order, Orderby, PasteListConds, TimeCondition},
+    paste::{NewPaste, PasteChangeset, Visibility},
+};
+use crate::services::paste::issue_edit_token;
+use crate::stores::{memory::MemoryStore, PasteStore};
+
+fn create_store() -> MemoryStore {
+    let store = MemoryStore::default();
+    let now = SystemTime::now();
+    let past = now - Duration::from_secs(60);
+    let pastes = vec![
+        ("deploy runbook", "step 1", "deploy", false, None),
+        ("Deploy notes", "50% done", "notes", false, None),
+        ("secret", "one shot", "secret", true, None),
+        ("stale", "gone", "stale", false, Some(past)),
+    ];
+
+    for (title, body, slug, burn_after_read, expires_at) in pastes {
+        store
+            .create(&NewPaste {
+                title,
+                body,
+                created_at: &now,
+                modified_at: &now,
+                expires_at: expires_at.as_ref(),
+                burn_after_read,
+                slug,
+                language: None,
+                password_hash: None,
+                cipher: None,
+                nonce: None,
+                key_id: None,
+                data_key: None,
+                visibility: Visibility::Public,
+                access_token_hash: None,
+                edit_token_hash: None,
+            }).unwrap()
+            .unwrap();
+    }
+
+    store
+}
+
+#[test]
+fn test_memory_store_slug_taken() {
+    let store = create_store();
+    let now = SystemTime::now();
+
+    // expired pastes keep their slug until purged
+    let created = store
+        .create(&NewPaste {
+            title: "again",
+            body: "again",
+            created_at: &now,
+            modified_at: &now,
+            expires_at: None,
+            burn_after_read: false,
+            slug: "stale",
+            language: None,
+            password_hash: None,
+            cipher: None,
+            nonce: None,
+            key_id: None,
+            data_key: None,
+            visibility: Visibility::Public,
+            access_token_hash: None,
+            edit_token_hash: None,
+        }).unwrap();
+    assert!(created.is_none());
+}
+
+#[test]
+fn test_memory_store_get() {
+    let store = create_store();
+
+    assert_eq!(store.get("deploy").unwrap().body, "step 1");
+    assert!(store.get("stale").is_err());
+
+    assert!(store.get_head("secret").is_err());
+    assert_eq!(store.get("secret").unwrap().body, "one shot");
+    assert!(store.get("secret").is_err());
+}
+
+#[test]
+fn test_memory_store_issue_edit_token() {
+    let store = create_store();
+
+    // pastes without an edit token get one, later tokens replace the earlier ones
+    let first = issue_edit_token(&store, "deploy").unwrap();
+    let second = issue_edit_token(&store, "deploy").unwrap();
+    let hashed = store.get_head("deploy").unwrap().edit_token_hash.unwrap();
+    assert!(token::verify(&hashed, &second));
+    assert!(!token::verify(&hashed, &first));
+
+    assert!(issue_edit_token(&store, "unknown").is_err());
+}
+
+#[test]
+fn test_memory_store_list() {
+    let store = create_store();
+
+    let found = store
+        .list(&PasteListConds {
+            title_pat: Some("deploy".to_owned()),
+            orderby_list: Some(vec![Orderby {
+                item: Item::Title,
+                order: Order::Ascend,
+            }]),
+            ..Default::default()
+        }).unwrap();
+    let titles = found.iter().map(|paste| paste.title.as_str()).collect::<Vec<_>>();
+    assert_eq!(titles, vec!["Deploy notes", "deploy runbook"]);
+
+    // counts ignore paging
+    let total = store
+        .count(&PasteListConds {
+            title_pat: Some("deploy".to_owned()),
+            limit: Some(1),
+            ..Default::default()
+        }).unwrap();
+    assert_eq!(total, 2);
+
+    // like wildcards in patterns behave as they do in sql
+    let found = store
+        .list(&PasteListConds {
+            body_pat: Some("5_\\% d".to_owned()),
+            ..Default::default()
+        }).unwrap();
+    assert_eq!(found.len(), 1);
+    let found = store
+        .list(&PasteListConds {
+            body_pat: Some("5_\\%done".to_owned()),
+            ..Default::default()
+        }).unwrap();
+    assert!(found.is_empty());
+
+    let found = store
+        .list(&PasteListConds {
+            created_at: Some(TimeCondition::Cmp(CmpOp::GT, SystemTime::now())),
+            ..Default::default()
+        }).unwrap();
+    assert!(found.is_empty());
+    let found = store
+        .list(&PasteListConds {
+            created_at: Some(TimeCondition::Between(UNIX_EPOCH, SystemTime::now())),
+            ..Default::default()
+        }).unwrap();
+    assert!(!found.is_empty());
+
+    let found = store
+        .list(&PasteListConds {
+            limit: Some(1),
+            offset: Some(1),
+            ..Default::default()
+        }).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].slug, "notes");
+}
+
+#[test]
+fn test_memory_store_list_with_match_mode() {
+    let store = create_store();
+    let list_titles = |title_pat: &str, title_mode| {
+        let mut titles = store
+            .list(&PasteListConds {
+                title_pat: Some(title_pat.to_owned()),
+                title_mode,
+                ..Default::default()
+            }).unwrap()
+            .into_iter()
+            .map(|paste| paste.title)
+            .collect::<Vec<_>>();
+        titles.sort();
+        titles
+    };
+
+    assert_eq!(list_titles("NOTES", MatchMode::Contains), vec!["Deploy notes"]);
+    assert_eq!(list_titles("deploy_", MatchMode::Contains), Vec::<String>::new());
+    assert_eq!(list_titles("%", MatchMode::Contains), Vec::<String>::new());
+    assert_eq!(list_titles("deploy notes", MatchMode::Exact), Vec::<String>::new());
+    assert_eq!(list_titles("Deploy notes", MatchMode::Exact), vec!["Deploy notes"]);
+    assert_eq!(
+        list_titles("^deploy (n|r)", MatchMode::Regex),
+        vec!["Deploy notes", "deploy runbook"]
+    );
+    assert_eq!(
+        list_titles("deploy runbok", MatchMode::Fuzzy),
+        vec!["Deploy notes", "deploy runbook"]
+    );
+    assert_eq!(list_titles("runbok", MatchMode::Fuzzy), vec!["deploy runbook"]);
+
+    let found = store.list(&PasteListConds {
+        body_pat: Some("(".to_owned()),
+        body_mode: MatchMode::Regex,
+        ..Default::default()
+    });
+    assert!(found.is_err());
+}
+
+#[test]
+fn test_memory_store_revisions() {
+    let store = create_store();
+    let now = SystemTime::now();
+
+    store
+        .update(
+            "deploy",
+            &PasteChangeset {
+                title: "deploy runbook",
+                body: "step 2",
+                language: None,
+                modified_at: &now,
+            },
+            None,
+        ).unwrap();
+
+    let revisions = store.list_revisions("deploy").unwrap();
+    assert_eq!(revisions.len(), 2);
+    assert_eq!(store.get_revision("deploy", 1).unwrap().body, "step 1");
+    assert_eq!(store.get_revision("deploy", 2).unwrap().body, "step 2");
+
+    assert_eq!(store.delete("deploy", None).unwrap(), 1);
+    assert!(store.list_revisions("deploy").is_err());
+}
+
+#[test]
+fn test_memory_store_versions() {
+    let store = create_store();
+    let now = SystemTime::now();
+    let changeset = PasteChangeset {
+        title: "deploy runbook",
+        body: "step 2",
+        language: None,
+        modified_at: &now,
+    };
+
+    assert_eq!(store.get_head("deploy").unwrap().version, 1);
+    match store.update("deploy", &changeset, Some(&[2])) {
+        Err(ServerError::VersionMismatch) => {}
+        res => panic!("unexpected update result: {:?}", res),
+    }
+    assert_eq!(store.list_revisions("deploy").unwrap().len(), 1);
+
+    let paste = store.update("deploy", &changeset, Some(&[1, 3])).unwrap();
+    assert_eq!(paste.version, 2);
+    assert_eq!(store.update("deploy", &changeset, None).unwrap().version, 3);
+
+    match store.delete("deploy", Some(&[2])) {
+        Err(ServerError::VersionMismatch) => {}
+        res => panic!("unexpected delete result: {:?}", res),
+    }
+    assert_eq!(store.delete("deploy", Some(&[3])).unwrap(), 1);
+    match store.update("deploy", &changeset, Some(&[3])) {
+        Err(ServerError::VersionMismatch) => {}
+        res => panic!("unexpected update result: {:?}", res),
+    }
+}
+
+#[test]
+fn test_memory_store_purge_expired() {
+    let store = create_store();
+
+    assert_eq!(store.purge_expired(SystemTime::now(), 500).unwrap(), 1);
+    assert_eq!(store.purge_expired(SystemTime::now(), 500).unwrap(), 0);
+}
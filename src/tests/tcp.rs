@@ -0,0 +1,117 @@
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+
+use actix_web::test::TestServer;
+use actix_web::{http::Method, App, HttpMessage};
+use serde_json;
+
+use crate::apps::paste as paste_app;
+use crate::common::config::TcpConfig;
+use crate::models::paste::Paste;
+use crate::server::State;
+use crate::tests::TEST_SUIT;
+
+const PASTE_URL_BASE: &str = "http://paste.test/pastes/";
+
+fn create_app() -> App<State> {
+    paste_app::create(State {
+        db_chan: TEST_SUIT.executor(),
+        slug_length: TEST_SUIT.config().paste.slug_length,
+    })
+}
+
+fn start_listener(max_size: usize, max_connections: usize) -> SocketAddr {
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    let (tx, rx) = channel();
+
+    thread::spawn(move || {
+        let sys = actix::System::new("pastebin-tcp-test");
+
+        let config = TcpConfig {
+            ip: "127.0.0.1".to_owned(),
+            // let the os pick a free port
+            port: "0".to_owned(),
+            max_size,
+            timeout: 5,
+            max_connections,
+            url: None,
+        };
+        let addr = crate::tcp::listen(
+            &config,
+            TEST_SUIT.executor(),
+            TEST_SUIT.config().paste.slug_length,
+            PASTE_URL_BASE.to_owned(),
+        ).unwrap();
+        tx.send(addr).unwrap();
+
+        sys.run();
+    });
+
+    rx.recv().unwrap()
+}
+
+fn send_raw(addr: &SocketAddr, body: &[u8]) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(body).unwrap();
+    stream.shutdown(Shutdown::Write).unwrap();
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).unwrap();
+    reply
+}
+
+#[test]
+fn test_tcp_create_paste() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let addr = start_listener(1024, 8);
+
+    let reply = send_raw(&addr, b"piped through nc\n");
+    assert!(reply.starts_with(PASTE_URL_BASE));
+    assert!(reply.ends_with('\n'));
+    let mut lines = reply.lines();
+    let slug = &lines.next().unwrap()[PASTE_URL_BASE.len()..];
+    assert!(lines.next().unwrap().starts_with("edit_token: "));
+
+    let mut srv = TestServer::with_factory(create_app);
+    let req = srv
+        .client(Method::GET, &format!("/pastes/{}", slug))
+        .finish()
+        .unwrap();
+
+    assert_res!(srv, req, Paste, |paste: Paste| {
+        assert_eq!(paste.slug, slug);
+        assert_eq!(paste.body, "piped through nc\n");
+    });
+}
+
+#[test]
+fn test_tcp_create_paste_too_large() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let addr = start_listener(8, 8);
+
+    let reply = send_raw(&addr, b"more than eight bytes");
+    assert_eq!(reply, "error: bad payload: paste body is too large\n");
+}
+
+#[test]
+fn test_tcp_too_many_connections() {
+    let _lock = TEST_SUIT.begin_isolated_test();
+    let addr = start_listener(1024, 1);
+
+    // the first connection stays open while the second one comes in
+    let mut first = TcpStream::connect(addr).unwrap();
+    first.write_all(b"first").unwrap();
+    let reply = send_raw(&addr, b"second");
+    assert_eq!(reply, "error: too many connections, try again later\n");
+
+    first.shutdown(Shutdown::Write).unwrap();
+    let mut reply = String::new();
+    first.read_to_string(&mut reply).unwrap();
+    assert!(reply.starts_with(PASTE_URL_BASE));
+
+    // closed connections make room again
+    let reply = send_raw(&addr, b"third");
+    assert!(reply.starts_with(PASTE_URL_BASE));
+}
@@ -0,0 +1,41 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::common::time::parse_at;
+
+#[test]
+fn test_parse_time() {
+    let now = UNIX_EPOCH + Duration::from_secs(1_767_225_600);
+
+    assert_eq!(parse_at("100000", now), Some(UNIX_EPOCH + Duration::from_secs(100_000)));
+    assert_eq!(parse_at("2026-01-01", now), Some(now));
+    assert_eq!(parse_at("2026-01-01T08:00:00+08:00", now), Some(now));
+    assert_eq!(
+        parse_at("2026-01-01T00:00:01.5Z", now),
+        Some(now + Duration::from_millis(1500))
+    );
+    assert_eq!(parse_at("-90s", now), Some(now - Duration::from_secs(90)));
+    assert_eq!(parse_at("-2h", now), Some(now - Duration::from_secs(7200)));
+    assert_eq!(parse_at("-7d", now), Some(now - Duration::from_secs(7 * 86_400)));
+    assert_eq!(parse_at("-1w", now), Some(now - Duration::from_secs(604_800)));
+}
+
+#[test]
+fn test_parse_bad_time() {
+    let now = UNIX_EPOCH + Duration::from_secs(1_767_225_600);
+
+    for time_str in &[
+        "",
+        "yesterday",
+        "2026-13-01",
+        "2026-01-01T00:00:00",
+        "-",
+        "-d",
+        "-7",
+        "-7y",
+        "-1.5h",
+        "18446744073709551615",
+        "-99999999999999999999w",
+    ] {
+        assert_eq!(parse_at(time_str, now), None, "{}", time_str);
+    }
+}